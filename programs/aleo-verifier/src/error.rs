@@ -2,7 +2,7 @@ use thiserror::Error;
 
 use solana_program::program_error::ProgramError;
 
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum EclipseError {
     /// Invalid instruction
     #[error("Invalid Instruction")]
@@ -11,6 +11,18 @@ pub enum EclipseError {
     NotRentExempt,
     #[error("InvalidStateAccount")]
     InvalidStateAccount,
+    #[error("InvalidTxDataAccount")]
+    InvalidTxDataAccount,
+    #[error("UnsealedTxData")]
+    UnsealedTxData,
+    #[error("TxIdMismatch")]
+    TxIdMismatch,
+    #[error("InvalidRegistryAccount")]
+    InvalidRegistryAccount,
+    #[error("InvalidConfigAccount")]
+    InvalidConfigAccount,
+    #[error("InvalidUpgradeAuthority")]
+    InvalidUpgradeAuthority,
 }
 
 impl From<EclipseError> for ProgramError {
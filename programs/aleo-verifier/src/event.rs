@@ -0,0 +1,44 @@
+//! Events logged through `sol_log_data` so that indexers can follow
+//! verification outcomes without parsing free-text logs. They are encoded
+//! like the uploader's events, see [`eclipse_uploader::event`].
+
+pub use eclipse_uploader::event::Event;
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::pubkey::Pubkey,
+};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
+pub enum VerifierEvent {
+    /// An Aleo transaction was verified and its record stored.
+    TxVerified {
+        tx_id: [u8; 32],
+        authority: Pubkey,
+        record: Pubkey,
+        tx_data: Pubkey,
+    },
+}
+
+impl Event for VerifierEvent {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_log_message() {
+        let event = VerifierEvent::TxVerified {
+            tx_id: [7; 32],
+            authority: Pubkey::new_unique(),
+            record: Pubkey::new_unique(),
+            tx_data: Pubkey::new_unique(),
+        };
+
+        let message = format!(
+            "Program data: {}",
+            base64::encode(event.try_to_vec().unwrap())
+        );
+        assert_eq!(VerifierEvent::from_log_message(&message), Some(event));
+        assert_eq!(VerifierEvent::from_log_message("Program log: hi"), None);
+    }
+}
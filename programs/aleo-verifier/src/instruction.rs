@@ -0,0 +1,328 @@
+use {
+    crate::{
+        processor::ALEO_VERIFIER,
+        state::{find_config_address, find_registry_address, find_verified_address, AleoTxContext},
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{
+        bpf_loader_upgradeable,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program,
+    },
+    std::str::FromStr,
+};
+
+/// Largest number of transactions in a `VerifyBatch` instruction. Each item
+/// takes three accounts and its own data, so larger batches would not fit
+/// into a transaction along with the shared accounts.
+pub const MAX_BATCH_SIZE: usize = 4;
+
+/// A transaction to verify as part of a `VerifyBatch` instruction.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BatchItem {
+    /// Id of the Aleo transaction.
+    pub tx_id: [u8; 32],
+    /// Aleo block and ledger root of the transaction.
+    pub context: AleoTxContext,
+}
+
+/// Outcome of a `VerifyBatch` item. The instruction returns the Borsh-encoded
+/// `Vec<BatchItemResult>` of its items, in order, as return data.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BatchItemResult {
+    /// The transaction was verified and its record stored.
+    Verified,
+    /// The authority had already verified the transaction.
+    AlreadyVerified,
+    /// The transaction data failed the bucket checks, or held another
+    /// transaction than `tx_id`, with the `EclipseError` code of the failed
+    /// check.
+    Skipped { error: u32 },
+}
+
+/// Prefix of the log messages carrying the return data of a program.
+const PROGRAM_RETURN_PREFIX: &str = "Program return: ";
+
+impl BatchItemResult {
+    /// Decodes the results of a `VerifyBatch` instruction of the verifier at
+    /// `program_id` from the logs of its transaction, such as those of a
+    /// simulation.
+    pub fn from_logs(program_id: &Pubkey, logs: &[String]) -> Option<Vec<Self>> {
+        let prefix = format!("{}{} ", PROGRAM_RETURN_PREFIX, program_id);
+        logs.iter().rev().find_map(|log| {
+            let data = base64::decode(log.strip_prefix(&prefix)?).ok()?;
+            Vec::<Self>::try_from_slice(&data).ok()
+        })
+    }
+}
+
+/// Instructions of the verifier program, Borsh-encoded. The leading variant
+/// index tells instructions apart, so variants must only ever be appended and
+/// an existing variant's fields never changed; a changed instruction gets a
+/// new variant instead.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
+pub enum VerifierInstruction {
+    /// Verify an Aleo transaction through the native Aleo verifier, store an
+    /// `AleoVerified` record for it and attest it in the transaction's
+    /// `AleoTxRegistry`, creating the registry on first verification.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[SIGNER, WRITE]`: The account of the account initialise the verification
+    /// 1. `[WRITE]`: Aleo transactions verification results storage account
+    /// 2. `[]`: Sealed uploader bucket holding the serialized Aleo transaction
+    /// 3. `[]`: Aleo Program account
+    /// 4. `[]`: System Program account
+    /// 5. `[WRITE]`: Registry of the Aleo transaction's verifications
+    /// 6. `[]`: Verifier config, see `SetConfig`
+    VerifyTransaction {
+        /// Id of the Aleo transaction.
+        tx_id: [u8; 32],
+    },
+
+    /// Same as `VerifyTransaction`, additionally storing the Aleo state the
+    /// transaction was verified against in the record.
+    ///
+    /// Accounts expected are the same as for `VerifyTransaction`.
+    VerifyTransactionInBlock {
+        /// Id of the Aleo transaction.
+        tx_id: [u8; 32],
+        /// Aleo block and ledger root of the transaction.
+        context: AleoTxContext,
+    },
+
+    /// Verify up to `MAX_BATCH_SIZE` Aleo transactions, as with
+    /// `VerifyTransactionInBlock` for each of them, and return their
+    /// `BatchItemResult`s.
+    ///
+    /// Items already verified by the authority, or whose transaction data
+    /// fails the checks or holds another transaction, are skipped. Invalid
+    /// record or registry addresses, as well as proofs rejected by the
+    /// native verifier, fail the whole instruction.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[SIGNER, WRITE]`: The account of the account initialise the verification
+    /// 1. `[]`: Aleo Program account
+    /// 2. `[]`: System Program account
+    /// 3. `[]`: Verifier config, see `SetConfig`
+    ///
+    /// Followed by, for each item in order:
+    ///
+    /// 0. `[WRITE]`: Aleo transactions verification results storage account
+    /// 1. `[]`: Sealed uploader bucket holding the serialized Aleo transaction
+    /// 2. `[WRITE]`: Registry of the Aleo transaction's verifications
+    VerifyBatch { items: Vec<BatchItem> },
+
+    /// Create or update the verifier config, which tells the verifier which
+    /// uploader program to read transactions from and what the native Aleo
+    /// verifier returns. Only the upgrade authority of the verifier program
+    /// may set it.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[SIGNER, WRITE]`: Upgrade authority of the verifier program, paying for the config
+    /// 1. `[WRITE]`: Verifier config, see `find_config_address`
+    /// 2. `[]`: Program data account of the verifier program
+    /// 3. `[]`: System Program account
+    SetConfig {
+        /// Uploader program whose sealed buckets transactions are read from.
+        uploader_program_id: Pubkey,
+        /// Interface version of the native Aleo verifier, see
+        /// `VerifierConfig::TX_ID_RETURNED`.
+        aleo_verifier_version: u8,
+    },
+}
+
+/// Creates a `VerifyTransactionInBlock` instruction, returning it along with
+/// the address of the verification record.
+pub fn verify_transaction(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    tx_data: &Pubkey,
+    tx_id: [u8; 32],
+    context: AleoTxContext,
+) -> (Instruction, Pubkey) {
+    let (record, _) = find_verified_address(program_id, &tx_id, authority);
+    let (registry, _) = find_registry_address(program_id, &tx_id);
+    let aleo_verifier_id = Pubkey::from_str(ALEO_VERIFIER).expect("failed to set program_id");
+    let instruction = Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyTransactionInBlock { tx_id, context },
+        vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(record, false),
+            AccountMeta::new_readonly(*tx_data, false),
+            AccountMeta::new_readonly(aleo_verifier_id, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(registry, false),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        ],
+    );
+
+    (instruction, record)
+}
+
+/// Creates a `VerifyBatch` instruction for items paired with the bucket
+/// holding their transaction, returning it along with the addresses of the
+/// verification records.
+pub fn verify_batch(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    items: &[(Pubkey, BatchItem)],
+) -> (Instruction, Vec<Pubkey>) {
+    let aleo_verifier_id = Pubkey::from_str(ALEO_VERIFIER).expect("failed to set program_id");
+    let mut accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(aleo_verifier_id, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+
+    let mut records = Vec::with_capacity(items.len());
+    for (tx_data, item) in items {
+        let (record, _) = find_verified_address(program_id, &item.tx_id, authority);
+        let (registry, _) = find_registry_address(program_id, &item.tx_id);
+        accounts.extend([
+            AccountMeta::new(record, false),
+            AccountMeta::new_readonly(*tx_data, false),
+            AccountMeta::new(registry, false),
+        ]);
+        records.push(record);
+    }
+
+    let instruction = Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyBatch {
+            items: items.iter().map(|(_, item)| *item).collect(),
+        },
+        accounts,
+    );
+
+    (instruction, records)
+}
+
+/// Finds the program data account of the upgradeable program at
+/// `program_id`.
+pub fn find_program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// Creates a `SetConfig` instruction signed by the verifier's
+/// `upgrade_authority`.
+pub fn set_config(
+    program_id: &Pubkey,
+    upgrade_authority: &Pubkey,
+    uploader_program_id: Pubkey,
+    aleo_verifier_version: u8,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::SetConfig {
+            uploader_program_id,
+            aleo_verifier_version,
+        },
+        vec![
+            AccountMeta::new(*upgrade_authority, true),
+            AccountMeta::new(config, false),
+            AccountMeta::new_readonly(find_program_data_address(program_id), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_program::message::Message};
+
+    /// Largest serialized transaction, as defined by the SDK.
+    const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
+
+    #[test]
+    fn test_verify_transaction_encoding() {
+        let instruction = VerifierInstruction::VerifyTransaction { tx_id: [7; 32] };
+
+        let bs = instruction.try_to_vec().unwrap();
+        assert_eq!(bs, [&[0][..], &[7; 32]].concat());
+        assert_eq!(
+            VerifierInstruction::try_from_slice(&bs).unwrap(),
+            instruction
+        );
+    }
+
+    #[test]
+    fn test_verify_transaction_in_block_encoding() {
+        let context = AleoTxContext {
+            block_height: 0x0102,
+            block_hash: [8; 32],
+            ledger_root: [9; 32],
+        };
+        let instruction = VerifierInstruction::VerifyTransactionInBlock {
+            tx_id: [7; 32],
+            context,
+        };
+
+        let bs = instruction.try_to_vec().unwrap();
+        assert_eq!(
+            bs,
+            [&[1][..], &[7; 32], &[2, 1, 0, 0], &[8; 32], &[9; 32]].concat()
+        );
+        assert_eq!(
+            VerifierInstruction::try_from_slice(&bs).unwrap(),
+            instruction
+        );
+    }
+
+    #[test]
+    fn test_full_batch_fits_transaction() {
+        let authority = Pubkey::new_unique();
+        let items: Vec<_> = (0..MAX_BATCH_SIZE as u8)
+            .map(|i| {
+                let item = BatchItem {
+                    tx_id: [i; 32],
+                    context: AleoTxContext::default(),
+                };
+                (Pubkey::new_unique(), item)
+            })
+            .collect();
+
+        let (instruction, records) = verify_batch(&Pubkey::new_unique(), &authority, &items);
+        assert_eq!(records.len(), MAX_BATCH_SIZE);
+        assert_eq!(instruction.accounts.len(), 4 + 3 * MAX_BATCH_SIZE);
+
+        // A single signature, prefixed by the signature count.
+        let message = Message::new(&[instruction], Some(&authority));
+        assert!(1 + 64 + message.serialize().len() <= PACKET_DATA_SIZE);
+    }
+
+    #[test]
+    fn test_batch_results_from_logs() {
+        let program_id = Pubkey::new_unique();
+        let results = vec![
+            BatchItemResult::Verified,
+            BatchItemResult::Skipped { error: 5 },
+        ];
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            format!(
+                "Program return: {} {}",
+                Pubkey::new_unique(),
+                base64::encode(vec![BatchItemResult::AlreadyVerified].try_to_vec().unwrap())
+            ),
+            format!(
+                "Program return: {} {}",
+                program_id,
+                base64::encode(results.try_to_vec().unwrap())
+            ),
+            format!("Program {} success", program_id),
+        ];
+
+        assert_eq!(
+            BatchItemResult::from_logs(&program_id, &logs),
+            Some(results)
+        );
+        assert_eq!(BatchItemResult::from_logs(&program_id, &logs[..1]), None);
+    }
+}
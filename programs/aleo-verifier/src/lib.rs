@@ -1,4 +1,7 @@
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
-mod error;
-mod processor;
-mod state;
+pub mod error;
+pub mod event;
+pub mod instruction;
+pub mod processor;
+pub mod state;
@@ -1,35 +1,370 @@
 use {
-    crate::{error::EclipseError, state::AleoVerified},
+    crate::{
+        error::EclipseError,
+        event::{Event, VerifierEvent},
+        instruction::{
+            find_program_data_address, BatchItem, BatchItemResult, VerifierInstruction,
+            MAX_BATCH_SIZE,
+        },
+        state::{
+            find_config_address, find_registry_address, find_verified_address, AleoTxContext,
+            AleoTxRegistry, AleoVerified, VerifierConfig,
+        },
+    },
     borsh::{BorshDeserialize, BorshSerialize},
+    eclipse_uploader::state::{BucketKind, DataBucketMeta},
     solana_program::{
         account_info::{next_account_info, AccountInfo},
+        bpf_loader_upgradeable,
         entrypoint::ProgramResult,
         instruction::{AccountMeta, Instruction},
         msg,
-        program::invoke_signed,
+        program::{get_return_data, invoke, invoke_signed, set_return_data},
+        program_error::ProgramError,
         pubkey::Pubkey,
         system_instruction,
-        sysvar::{rent::Rent, Sysvar},
+        sysvar::{clock::Clock, rent::Rent, Sysvar},
     },
     std::str::FromStr,
 };
 
-const ALEO_VERIFIER: &str = "A1eoProof1111111111111111111111111111111111";
+/// Address of the native program verifying Aleo transaction proofs.
+pub const ALEO_VERIFIER: &str = "A1eoProof1111111111111111111111111111111111";
+
+/// Length of the bincode `UpgradeableLoaderState::ProgramData` starting
+/// program data accounts: the little-endian `u32` variant index, the `u64`
+/// deployment slot and the optional upgrade authority.
+const PROGRAM_DATA_METADATA_LEN: usize = 4 + 8 + 1 + 32;
+
+/// Variant index of `UpgradeableLoaderState::ProgramData`.
+const PROGRAM_DATA_STATE: u32 = 3;
+
+/// Ensures `authority_account` signed as the upgrade authority of the
+/// verifier, as recorded in its program data account.
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    authority_account: &AccountInfo,
+    program_data_account: &AccountInfo,
+) -> ProgramResult {
+    if *program_data_account.key != find_program_data_address(program_id)
+        || *program_data_account.owner != bpf_loader_upgradeable::id()
+    {
+        msg!("Program data account does not belong to the verifier");
+        return Err(EclipseError::InvalidUpgradeAuthority.into());
+    }
+
+    let program_data = program_data_account.try_borrow_data()?;
+    let upgrade_authority = match program_data.get(..PROGRAM_DATA_METADATA_LEN) {
+        Some(metadata)
+            if metadata[..4] == PROGRAM_DATA_STATE.to_le_bytes() && metadata[12] == 1 =>
+        {
+            Pubkey::new(&metadata[13..])
+        }
+        _ => {
+            msg!("Verifier program is not upgradeable");
+            return Err(EclipseError::InvalidUpgradeAuthority.into());
+        }
+    };
+
+    if !authority_account.is_signer || *authority_account.key != upgrade_authority {
+        msg!("Config must be signed by the upgrade authority");
+        return Err(EclipseError::InvalidUpgradeAuthority.into());
+    }
+
+    Ok(())
+}
+
+/// Reads the verifier config, which must have been set through `SetConfig`.
+fn load_config(
+    program_id: &Pubkey,
+    config_account: &AccountInfo,
+) -> Result<VerifierConfig, ProgramError> {
+    let (config_pda, _) = find_config_address(program_id);
+    if *config_account.key != config_pda || config_account.owner != program_id {
+        msg!("Verifier config is not set");
+        return Err(EclipseError::InvalidConfigAccount.into());
+    }
+
+    VerifierConfig::try_from_slice(&config_account.try_borrow_data()?)
+        .map_err(|_| EclipseError::InvalidConfigAccount.into())
+}
+
+/// Ensures the transaction data comes from a sealed bucket of the configured
+/// uploader program, so that it cannot change once verified.
+fn check_tx_data(tx_data_account: &AccountInfo, config: &VerifierConfig) -> ProgramResult {
+    if *tx_data_account.owner != config.uploader_program_id {
+        msg!("Transaction data must be stored in an uploader bucket");
+        return Err(EclipseError::InvalidTxDataAccount.into());
+    }
+
+    let bucket_data = tx_data_account.try_borrow_data()?;
+    let meta =
+        DataBucketMeta::unpack(&bucket_data).map_err(|_| EclipseError::InvalidTxDataAccount)?;
+    if !meta.sealed {
+        msg!("Transaction data bucket must be sealed");
+        return Err(EclipseError::UnsealedTxData.into());
+    }
+
+    if meta.kind != BucketKind::Data {
+        msg!("Transaction data must be stored in a single bucket");
+        return Err(EclipseError::InvalidTxDataAccount.into());
+    }
+
+    Ok(())
+}
+
+/// Calls the native Aleo verifier, which fails the instruction unless the
+/// transaction proofs are valid.
+fn invoke_aleo_verifier<'a>(
+    program_id: &Pubkey,
+    aleo_program: &AccountInfo<'a>,
+    tx_data_account: &AccountInfo<'a>,
+    tx_id: &[u8; 32],
+) -> ProgramResult {
+    let aleo_verifier_id = Pubkey::from_str(ALEO_VERIFIER).expect("failed to set program_id");
+    let instruction = Instruction::new_with_bytes(
+        aleo_verifier_id,
+        tx_id,
+        vec![AccountMeta::new_readonly(*tx_data_account.key, false)],
+    );
+    let (_, bump_seed) = Pubkey::find_program_address(&[b"eclipse"], program_id);
+    invoke_signed(
+        &instruction,
+        &[aleo_program.clone(), tx_data_account.clone()],
+        &[&[&b"eclipse"[..], &[bump_seed]]],
+    )
+}
+
+/// Ensures the native Aleo verifier, just invoked, verified the transaction
+/// `tx_id`.
+///
+/// The transaction id is derived from the transitions rather than being part
+/// of the serialized transaction, and deriving it takes snarkVM hashes not
+/// available to programs. Native verifiers from
+/// [`VerifierConfig::TX_ID_RETURNED`] on therefore return the id of the
+/// transaction they deserialized from the bucket and verified, binding the
+/// record to the verified bytes. Earlier ones return nothing, and the claimed
+/// id is taken as is.
+fn confirm_tx_id(config: &VerifierConfig, tx_id: &[u8; 32]) -> Result<(), EclipseError> {
+    if !config.confirms_tx_id() {
+        return Ok(());
+    }
+
+    let aleo_verifier_id = Pubkey::from_str(ALEO_VERIFIER).expect("failed to set program_id");
+    match get_return_data() {
+        Some((program_id, verified_tx_id))
+            if program_id == aleo_verifier_id && verified_tx_id == tx_id =>
+        {
+            Ok(())
+        }
+        _ => {
+            msg!("Transaction data does not hold the transaction being verified");
+            Err(EclipseError::TxIdMismatch)
+        }
+    }
+}
+
+/// Creates the account of `len` bytes owned by the program at the address
+/// derived from `seeds`, funded by `payer_account`.
+///
+/// Derived addresses are public, and anyone may fund them beforehand, which
+/// makes `create_account` fail. Such an account is topped up to be rent
+/// exempt, allocated and assigned instead.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer_account: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    len: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent_lamports = Rent::get()?.minimum_balance(len);
+
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer_account.key,
+                account.key,
+                rent_lamports,
+                len as u64,
+                program_id,
+            ),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program_account.clone(),
+            ],
+            &[seeds],
+        );
+    }
+
+    let required_lamports = rent_lamports.saturating_sub(account.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account.key, account.key, required_lamports),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+    }
+
+    invoke_signed(
+        &system_instruction::allocate(account.key, len as u64),
+        &[account.clone(), system_program_account.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program_account.clone()],
+        &[seeds],
+    )
+}
+
+/// Creates the `AleoVerified` record of a successfully verified transaction.
+#[allow(clippy::too_many_arguments)]
+fn store_record<'a>(
+    program_id: &Pubkey,
+    authority_account: &AccountInfo<'a>,
+    state_account: &AccountInfo<'a>,
+    tx_data_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    tx_id: &[u8; 32],
+    verified_acc_bump: u8,
+    context: AleoTxContext,
+) -> ProgramResult {
+    // Only successfully verified tx are stored.
+    create_pda_account(
+        program_id,
+        authority_account,
+        state_account,
+        system_program_account,
+        AleoVerified::LEN,
+        &[
+            b"AleoTx".as_ref(),
+            tx_id.as_ref(),
+            authority_account.key.as_ref(),
+            &[verified_acc_bump],
+        ],
+    )?;
+
+    let clock = Clock::get()?;
+    let state = AleoVerified {
+        version: AleoVerified::VERSION,
+        tx_id: *tx_id,
+        bump: verified_acc_bump,
+        authority: *authority_account.key,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+        context,
+        tx_data: *tx_data_account.key,
+    };
+    state.serialize(&mut &mut state_account.data.borrow_mut()[..])?;
+
+    msg!("New verified Aleo Tx Stored at {:?}", state_account.key);
+
+    VerifierEvent::TxVerified {
+        tx_id: state.tx_id,
+        authority: state.authority,
+        record: *state_account.key,
+        tx_data: *tx_data_account.key,
+    }
+    .emit();
+
+    Ok(())
+}
+
+/// Attests the verification of `tx_id` by `authority_account` in the
+/// transaction's registry, creating the registry on first verification.
+fn attest<'a>(
+    program_id: &Pubkey,
+    authority_account: &AccountInfo<'a>,
+    registry_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    tx_id: &[u8; 32],
+) -> ProgramResult {
+    let (registry_pda, registry_bump) = find_registry_address(program_id, tx_id);
+    if registry_pda != *registry_account.key {
+        return Err(EclipseError::InvalidRegistryAccount.into());
+    }
+
+    let mut registry = if registry_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            authority_account,
+            registry_account,
+            system_program_account,
+            AleoTxRegistry::LEN,
+            &[b"AleoTxRegistry".as_ref(), tx_id.as_ref(), &[registry_bump]],
+        )?;
+
+        AleoTxRegistry {
+            tx_id: *tx_id,
+            bump: registry_bump,
+            first_verifier: *authority_account.key,
+            count: 0,
+            relayers: Vec::new(),
+        }
+    } else {
+        if registry_account.owner != program_id {
+            return Err(EclipseError::InvalidRegistryAccount.into());
+        }
+        AleoTxRegistry::deserialize(&mut &registry_account.data.borrow()[..])?
+    };
+
+    registry.attest(*authority_account.key);
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+
+    msg!(
+        "Aleo Tx attested by {} relayers at {:?}",
+        registry.count,
+        registry_account.key
+    );
+
+    Ok(())
+}
 
 pub struct Processor;
 
 impl Processor {
-    /// Accounts expected:
-    ///
-    /// 0. `[SIGNER]`: The account of the account initialise the verification
-    /// 1. `[WRITE]`: Aleo transactions verification results storage account
-    /// 2. `[]`: Aleo transaction data storage account
-    /// 3. `[]`: Aleo Program account
-    /// 4. `[]`: System Program account
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         instruction_data: &[u8],
+    ) -> ProgramResult {
+        let instruction = VerifierInstruction::try_from_slice(instruction_data)
+            .map_err(|_| EclipseError::InvalidInstruction)?;
+
+        match instruction {
+            VerifierInstruction::VerifyTransaction { tx_id } => {
+                Self::verify_transaction(program_id, accounts, &tx_id, AleoTxContext::default())
+            }
+            VerifierInstruction::VerifyTransactionInBlock { tx_id, context } => {
+                Self::verify_transaction(program_id, accounts, &tx_id, context)
+            }
+            VerifierInstruction::VerifyBatch { items } => {
+                Self::verify_batch(program_id, accounts, &items)
+            }
+            VerifierInstruction::SetConfig {
+                uploader_program_id,
+                aleo_verifier_version,
+            } => Self::set_config(
+                program_id,
+                accounts,
+                uploader_program_id,
+                aleo_verifier_version,
+            ),
+        }
+    }
+
+    /// See [`VerifierInstruction::VerifyTransaction`] for the accounts
+    /// expected.
+    fn verify_transaction(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        tx_id: &[u8; 32],
+        context: AleoTxContext,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
@@ -38,77 +373,172 @@ impl Processor {
         let tx_data_account = next_account_info(account_info_iter)?;
         let aleo_program = next_account_info(account_info_iter)?;
         let system_program_account = next_account_info(account_info_iter)?;
+        let registry_account = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
 
-        let (verified_pda, verified_acc_bump) = Pubkey::find_program_address(
-            &[
-                b"AleoTx".as_ref(),
-                instruction_data,
-                authority_account.key.as_ref(),
-            ],
-            program_id,
-        );
+        let (verified_pda, verified_acc_bump) =
+            find_verified_address(program_id, tx_id, authority_account.key);
         if verified_pda != *state_account.key {
             return Err(EclipseError::InvalidStateAccount.into());
         }
 
+        let config = load_config(program_id, config_account)?;
+        check_tx_data(tx_data_account, &config)?;
+
         // Call AleoVerifier native program to verify that Aleo tx proofs are valid.
-        let aleo_verifier_id = Pubkey::from_str(ALEO_VERIFIER).expect("failed to set program_id");
-        let instruction = Instruction::new_with_bytes(
-            aleo_verifier_id,
-            instruction_data,
-            vec![AccountMeta::new_readonly(*tx_data_account.key, false)],
-        );
-        let (_, bump_seed) = Pubkey::find_program_address(&[b"eclipse"], program_id);
-        invoke_signed(
-            &instruction,
-            &[aleo_program.clone(), tx_data_account.clone()],
-            &[&[&b"eclipse"[..], &[bump_seed]]],
-        )?;
+        invoke_aleo_verifier(program_id, aleo_program, tx_data_account, tx_id)?;
+        confirm_tx_id(&config, tx_id)?;
 
-        //
         // AleoVerifier native program successfully returned. Now create PDA to store verification
         // result.
-        //
-        // Only successfully verified tx are stored.
-        // Length is bump for the account + authority_account + tx_id.
-        let stored_tx_len: usize = 1 + 32 + 32;
-
-        let rent = Rent::get()?;
-        let rent_lamports = rent.minimum_balance(stored_tx_len);
-
-        let create_tx_pda_ix = &system_instruction::create_account(
-            authority_account.key,
-            state_account.key,
-            rent_lamports,
-            stored_tx_len.try_into().unwrap(),
+        store_record(
             program_id,
-        );
-
-        invoke_signed(
-            create_tx_pda_ix,
-            &[
-                authority_account.clone(),
-                state_account.clone(),
-                system_program_account.clone(),
-            ],
-            &[&[
-                b"AleoTx".as_ref(),
-                instruction_data,
-                authority_account.key.as_ref(),
-                &[verified_acc_bump],
-            ]],
+            authority_account,
+            state_account,
+            tx_data_account,
+            system_program_account,
+            tx_id,
+            verified_acc_bump,
+            context,
         )?;
 
-        let mut state = AleoVerified::deserialize(&mut &state_account.data.borrow()[..])?;
-        state.tx_id = instruction_data
-            .try_into()
-            .map_err(|_| EclipseError::InvalidStateAccount)
-            .unwrap();
-        state.bump = bump_seed;
-        state.authority = *authority_account.key;
-        state.serialize(&mut &mut state_account.data.borrow_mut()[..])?;
+        attest(
+            program_id,
+            authority_account,
+            registry_account,
+            system_program_account,
+            tx_id,
+        )
+    }
+
+    /// See [`VerifierInstruction::VerifyBatch`] for the accounts expected.
+    fn verify_batch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        items: &[BatchItem],
+    ) -> ProgramResult {
+        if items.is_empty() || items.len() > MAX_BATCH_SIZE {
+            msg!("Batch must hold 1 to {} transactions", MAX_BATCH_SIZE);
+            return Err(EclipseError::InvalidInstruction.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
 
-        msg!("New verified Aleo Tx Stored at {:?}", state_account.key);
+        let authority_account = next_account_info(account_info_iter)?;
+        let aleo_program = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+
+        let config = load_config(program_id, config_account)?;
+
+        let mut results = Vec::with_capacity(items.len());
+        for item in items {
+            let state_account = next_account_info(account_info_iter)?;
+            let tx_data_account = next_account_info(account_info_iter)?;
+            let registry_account = next_account_info(account_info_iter)?;
+
+            let (verified_pda, verified_acc_bump) =
+                find_verified_address(program_id, &item.tx_id, authority_account.key);
+            if verified_pda != *state_account.key {
+                return Err(EclipseError::InvalidStateAccount.into());
+            }
+
+            // Retried batches skip the transactions verified the first time.
+            if !state_account.data_is_empty() {
+                results.push(BatchItemResult::AlreadyVerified);
+                continue;
+            }
+
+            match check_tx_data(tx_data_account, &config) {
+                Ok(()) => {}
+                Err(ProgramError::Custom(error)) => {
+                    results.push(BatchItemResult::Skipped { error });
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
+
+            invoke_aleo_verifier(program_id, aleo_program, tx_data_account, &item.tx_id)?;
+            if let Err(error) = confirm_tx_id(&config, &item.tx_id) {
+                results.push(BatchItemResult::Skipped {
+                    error: error as u32,
+                });
+                continue;
+            }
+
+            store_record(
+                program_id,
+                authority_account,
+                state_account,
+                tx_data_account,
+                system_program_account,
+                &item.tx_id,
+                verified_acc_bump,
+                item.context,
+            )?;
+
+            attest(
+                program_id,
+                authority_account,
+                registry_account,
+                system_program_account,
+                &item.tx_id,
+            )?;
+
+            results.push(BatchItemResult::Verified);
+        }
+
+        set_return_data(&results.try_to_vec()?);
+
+        Ok(())
+    }
+    /// See [`VerifierInstruction::SetConfig`] for the accounts expected.
+    fn set_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        uploader_program_id: Pubkey,
+        aleo_verifier_version: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let program_data_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+
+        check_upgrade_authority(program_id, authority_account, program_data_account)?;
+
+        let (config_pda, config_bump) = find_config_address(program_id);
+        if config_pda != *config_account.key {
+            return Err(EclipseError::InvalidConfigAccount.into());
+        }
+
+        if config_account.data_is_empty() {
+            create_pda_account(
+                program_id,
+                authority_account,
+                config_account,
+                system_program_account,
+                VerifierConfig::LEN,
+                &[b"VerifierConfig".as_ref(), &[config_bump]],
+            )?;
+        } else if config_account.owner != program_id {
+            return Err(EclipseError::InvalidConfigAccount.into());
+        }
+
+        let config = VerifierConfig {
+            version: VerifierConfig::VERSION,
+            bump: config_bump,
+            uploader_program_id,
+            aleo_verifier_version,
+        };
+        config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+
+        msg!(
+            "Verifier reads from uploader {} with Aleo verifier version {}",
+            uploader_program_id,
+            aleo_verifier_version
+        );
 
         Ok(())
     }
@@ -1,12 +1,183 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::pubkey::Pubkey;
+use solana_program::{
+    clock::{Slot, UnixTimestamp},
+    pubkey::Pubkey,
+};
+
+/// Aleo ledger state a transaction was verified against, as reported by the
+/// relayer.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct AleoTxContext {
+    /// Height of the Aleo block including the transaction.
+    pub block_height: u32,
+    /// Hash of the Aleo block including the transaction.
+    pub block_hash: [u8; 32],
+    /// Ledger root the transaction was created against.
+    pub ledger_root: [u8; 32],
+}
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
 pub struct AleoVerified {
+    /// Layout version, see [`AleoVerified::VERSION`].
+    pub version: u8,
     // If the tx_id is not the expected length,
     // it will not be verified therefore not stored
     pub tx_id: [u8; 32],
     pub bump: u8,
     // The public key that submitted the tx for verification
     pub authority: Pubkey,
+    /// Solana slot of the verification.
+    pub slot: Slot,
+    /// Solana cluster time of the verification.
+    pub timestamp: UnixTimestamp,
+    /// Aleo state of the transaction. Zeroed for records created through
+    /// `VerifyTransaction`, which does not carry it.
+    pub context: AleoTxContext,
+    /// Uploader bucket holding the verified transaction.
+    pub tx_data: Pubkey,
+}
+
+impl AleoVerified {
+    /// Current layout version.
+    pub const VERSION: u8 = 1;
+
+    /// Account size of a record.
+    pub const LEN: usize = 1 + 32 + 1 + 32 + 8 + 8 + (4 + 32 + 32) + 32;
+}
+
+/// Maximum number of relayers listed by an [`AleoTxRegistry`].
+pub const MAX_ATTESTATIONS: usize = 16;
+
+/// Canonical record of an Aleo transaction's verifications, shared by all
+/// relayers. Its address depends on the transaction id only, so consumers can
+/// find it without knowing which relayer verified the transaction.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug)]
+pub struct AleoTxRegistry {
+    pub tx_id: [u8; 32],
+    pub bump: u8,
+    /// The relayer that verified the transaction first.
+    pub first_verifier: Pubkey,
+    /// Number of relayers that verified the transaction, including those past
+    /// [`MAX_ATTESTATIONS`] and therefore not listed.
+    pub count: u32,
+    /// Relayers that verified the transaction, in verification order.
+    pub relayers: Vec<Pubkey>,
+}
+
+impl AleoTxRegistry {
+    /// Account size fitting [`MAX_ATTESTATIONS`] relayers.
+    pub const LEN: usize = 32 + 1 + 32 + 4 + 4 + 32 * MAX_ATTESTATIONS;
+
+    /// Records a verification by `relayer`.
+    pub fn attest(&mut self, relayer: Pubkey) {
+        self.count = self.count.saturating_add(1);
+        if self.relayers.len() < MAX_ATTESTATIONS {
+            self.relayers.push(relayer);
+        }
+    }
+}
+
+/// Deployment settings of the verifier, stored at [`find_config_address`]
+/// and set by the program's upgrade authority through `SetConfig`.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct VerifierConfig {
+    /// Layout version, see [`VerifierConfig::VERSION`].
+    pub version: u8,
+    pub bump: u8,
+    /// Uploader program whose sealed buckets transactions are read from.
+    pub uploader_program_id: Pubkey,
+    /// Interface version of the native Aleo verifier on the cluster, see
+    /// [`VerifierConfig::TX_ID_RETURNED`].
+    pub aleo_verifier_version: u8,
+}
+
+impl VerifierConfig {
+    /// Current layout version.
+    pub const VERSION: u8 = 1;
+
+    /// First native Aleo verifier version returning the id of the
+    /// transaction it verified, which is then checked against the claimed
+    /// one. Earlier versions only verify the proofs.
+    pub const TX_ID_RETURNED: u8 = 1;
+
+    /// Account size of the config.
+    pub const LEN: usize = 1 + 1 + 32 + 1;
+
+    /// Whether the native Aleo verifier returns the verified transaction id.
+    pub fn confirms_tx_id(&self) -> bool {
+        self.aleo_verifier_version >= Self::TX_ID_RETURNED
+    }
+}
+
+/// Finds the address of the verifier config.
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"VerifierConfig".as_ref()], program_id)
+}
+
+/// Finds the address of the registry of `tx_id`.
+pub fn find_registry_address(program_id: &Pubkey, tx_id: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"AleoTxRegistry".as_ref(), tx_id.as_ref()], program_id)
+}
+
+/// Finds the address of the record of `authority` verifying `tx_id`.
+pub fn find_verified_address(
+    program_id: &Pubkey,
+    tx_id: &[u8; 32],
+    authority: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"AleoTx".as_ref(), tx_id.as_ref(), authority.as_ref()],
+        program_id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_len() {
+        let record = AleoVerified {
+            version: AleoVerified::VERSION,
+            tx_id: [1; 32],
+            bump: 255,
+            authority: Pubkey::new_unique(),
+            slot: 1,
+            timestamp: 2,
+            context: AleoTxContext::default(),
+            tx_data: Pubkey::new_unique(),
+        };
+
+        assert_eq!(record.try_to_vec().unwrap().len(), AleoVerified::LEN);
+    }
+
+    #[test]
+    fn test_config_len() {
+        let config = VerifierConfig {
+            version: VerifierConfig::VERSION,
+            bump: 255,
+            uploader_program_id: Pubkey::new_unique(),
+            aleo_verifier_version: VerifierConfig::TX_ID_RETURNED,
+        };
+
+        assert_eq!(config.try_to_vec().unwrap().len(), VerifierConfig::LEN);
+    }
+
+    #[test]
+    fn test_full_registry_fits_account() {
+        let mut registry = AleoTxRegistry {
+            tx_id: [1; 32],
+            bump: 255,
+            first_verifier: Pubkey::new_unique(),
+            count: 0,
+            relayers: Vec::new(),
+        };
+        for _ in 0..MAX_ATTESTATIONS + 1 {
+            registry.attest(Pubkey::new_unique());
+        }
+
+        assert_eq!(registry.count as usize, MAX_ATTESTATIONS + 1);
+        assert_eq!(registry.relayers.len(), MAX_ATTESTATIONS);
+        assert_eq!(registry.try_to_vec().unwrap().len(), AleoTxRegistry::LEN);
+    }
 }
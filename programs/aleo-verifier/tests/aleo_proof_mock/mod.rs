@@ -0,0 +1,91 @@
+//! Stand-in for the native `A1eoProof` program, which only exists in the
+//! forked validator. Reads the transaction from the uploader bucket as the
+//! native program does, through the bincode `DataBucket` prefix, accepts it
+//! unless rejected, and returns its id.
+//!
+//! Real transaction ids are derived from the transitions with snarkVM hashes,
+//! so the mock derives them from the whole transaction with SHA-256 instead,
+//! see [`transaction`].
+
+use {
+    eclipse_aleo_verifier::processor::ALEO_VERIFIER,
+    eclipse_uploader::state::DATA_BUCKET_CONTENT_OFFSET,
+    solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        entrypoint::ProgramResult,
+        hash::hash,
+        program::set_return_data,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, ProgramTest},
+    std::{collections::HashSet, str::FromStr, sync::Mutex},
+};
+
+/// Error returned for rejected transactions.
+pub const REJECTED: u32 = 0xa1e0;
+
+lazy_static::lazy_static! {
+    static ref REJECTED_TX_IDS: Mutex<HashSet<[u8; 32]>> = Mutex::new(HashSet::new());
+    static ref LEGACY_TX_IDS: Mutex<HashSet<[u8; 32]>> = Mutex::new(HashSet::new());
+}
+
+/// Makes the mock reject `tx_id` from now on. Tests run concurrently, so each
+/// test should use its own transaction ids.
+pub fn reject(tx_id: [u8; 32]) {
+    REJECTED_TX_IDS.lock().unwrap().insert(tx_id);
+}
+
+/// Makes the mock verify `tx_id` like native verifiers predating
+/// `VerifierConfig::TX_ID_RETURNED`, returning nothing. Tests run
+/// concurrently, so each test should use its own transaction ids.
+pub fn legacy(tx_id: [u8; 32]) {
+    LEGACY_TX_IDS.lock().unwrap().insert(tx_id);
+}
+
+/// A serialized transaction distinct for each `seed`, along with the id the
+/// mock derives for it.
+pub fn transaction(seed: u8) -> ([u8; 32], Vec<u8>) {
+    let transaction = vec![seed; 100];
+    (hash(&transaction).to_bytes(), transaction)
+}
+
+pub fn id() -> Pubkey {
+    Pubkey::from_str(ALEO_VERIFIER).unwrap()
+}
+
+/// Registers the mock at the address of the native program.
+pub fn add_to(program_test: &mut ProgramTest) {
+    program_test.add_program("aleo_proof_mock", id(), processor!(process_instruction));
+}
+
+fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let tx_data_account = next_account_info(&mut accounts.iter())?;
+    if instruction_data.len() != 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let bucket_data = tx_data_account.try_borrow_data()?;
+    let len = bucket_data
+        .get(41..DATA_BUCKET_CONTENT_OFFSET)
+        .map(|len| u64::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let transaction = bucket_data[DATA_BUCKET_CONTENT_OFFSET..]
+        .get(..len)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    let tx_id = hash(transaction).to_bytes();
+    if REJECTED_TX_IDS.lock().unwrap().contains(&tx_id) {
+        return Err(ProgramError::Custom(REJECTED));
+    }
+
+    if !LEGACY_TX_IDS.lock().unwrap().contains(&tx_id) {
+        set_return_data(&tx_id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Program reading uploader buckets through [`eclipse_uploader::cpi`], as
+//! downstream programs do, and returning the bytes read.
+
+use {
+    eclipse_uploader::cpi,
+    solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::set_return_data,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, ProgramTest},
+};
+
+/// Registers the reader along with the uploader program it reads through,
+/// returning the reader's address.
+pub fn add_to(program_test: &mut ProgramTest) -> Pubkey {
+    program_test.add_program(
+        "eclipse_uploader",
+        eclipse_uploader::id(),
+        processor!(eclipse_uploader::process_instruction),
+    );
+
+    let reader_id = Pubkey::new_unique();
+    program_test.add_program("bucket_reader", reader_id, processor!(process_instruction));
+    reader_id
+}
+
+/// Reads `len` bytes at `offset` of `data_bucket`.
+pub fn read(reader_id: &Pubkey, data_bucket: &Pubkey, offset: u32, len: u32) -> Instruction {
+    Instruction {
+        program_id: *reader_id,
+        accounts: vec![
+            AccountMeta::new_readonly(eclipse_uploader::id(), false),
+            AccountMeta::new_readonly(*data_bucket, false),
+        ],
+        data: [offset.to_le_bytes(), len.to_le_bytes()].concat(),
+    }
+}
+
+fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let uploader_program = next_account_info(account_info_iter)?;
+    let data_bucket = next_account_info(account_info_iter)?;
+
+    if instruction_data.len() != 8 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (offset, len) = instruction_data.split_at(4);
+    let offset = u32::from_le_bytes(offset.try_into().unwrap());
+    let len = u32::from_le_bytes(len.try_into().unwrap());
+
+    let data = cpi::read_bucket(uploader_program, data_bucket, offset, len)?;
+    set_return_data(&data);
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Program invoking an instruction and checking the data it returns, as the
+//! banks client does not expose return data.
+
+use {
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::{get_return_data, invoke},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, ProgramTest},
+};
+
+/// Error returned when the invoked instruction returns unexpected data.
+pub const UNEXPECTED_RETURN_DATA: u32 = 0x5e7;
+
+/// Registers the probe, returning its address.
+pub fn add_to(program_test: &mut ProgramTest) -> Pubkey {
+    let probe_id = Pubkey::new_unique();
+    program_test.add_program(
+        "return_data_probe",
+        probe_id,
+        processor!(process_instruction),
+    );
+    probe_id
+}
+
+/// Wraps `instruction` into one of the probe at `probe_id`, which fails
+/// unless `instruction` returns `expected`.
+pub fn expect_return_data(
+    probe_id: &Pubkey,
+    instruction: Instruction,
+    expected: &[u8],
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(instruction.program_id, false)];
+    accounts.extend(instruction.accounts);
+    let data = [
+        &(expected.len() as u32).to_le_bytes(),
+        expected,
+        &instruction.data,
+    ]
+    .concat();
+
+    Instruction {
+        program_id: *probe_id,
+        accounts,
+        data,
+    }
+}
+
+fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (program, accounts) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if instruction_data.len() < 4 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (len, data) = instruction_data.split_at(4);
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    if data.len() < len {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (expected, data) = data.split_at(len);
+
+    let instruction = Instruction {
+        program_id: *program.key,
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+    invoke(
+        &instruction,
+        &[accounts, std::slice::from_ref(program)].concat(),
+    )?;
+
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *program.key && data == expected => Ok(()),
+        _ => Err(ProgramError::Custom(UNEXPECTED_RETURN_DATA)),
+    }
+}
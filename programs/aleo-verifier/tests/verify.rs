@@ -0,0 +1,785 @@
+mod aleo_proof_mock;
+mod bucket_reader;
+mod return_data_probe;
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    eclipse_aleo_verifier::{
+        error::EclipseError,
+        instruction::{self, BatchItem, BatchItemResult, VerifierInstruction},
+        processor::Processor,
+        state::{
+            find_config_address, find_registry_address, find_verified_address, AleoTxContext,
+            AleoTxRegistry, AleoVerified, VerifierConfig,
+        },
+    },
+    eclipse_uploader::{
+        error::UploaderError,
+        state::{data_bucket_len, BucketKind, DataBucketMeta, DATA_BUCKET_CONTENT_OFFSET},
+    },
+    solana_program::{
+        bpf_loader_upgradeable,
+        instruction::{Instruction, InstructionError},
+        pubkey::Pubkey,
+        system_instruction::SystemError,
+    },
+    solana_program_test::{processor, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        hash::Hash,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+/// Aleo state reported for the verified transactions.
+const TX_CONTEXT: AleoTxContext = AleoTxContext {
+    block_height: 42,
+    block_hash: [3; 32],
+    ledger_root: [4; 32],
+};
+
+/// Config of the verifier under test: the uploader program and the version of
+/// the native verifier, which the mock stands in for.
+const CONFIG: (Pubkey, u8) = (eclipse_uploader::ID, VerifierConfig::TX_ID_RETURNED);
+
+struct Context {
+    banks_client: BanksClient,
+    payer: Keypair,
+    authority: Keypair,
+    upgrade_authority: Keypair,
+    recent_blockhash: Hash,
+    program_id: Pubkey,
+    probe_id: Pubkey,
+    reader_id: Pubkey,
+}
+
+impl Context {
+    /// Starts a test ledger holding the given transaction data accounts.
+    async fn new(tx_data: Vec<(Pubkey, Account)>) -> Self {
+        Self::with_config(Some(CONFIG), tx_data).await
+    }
+
+    /// Starts a test ledger with the verifier configured with `config`, if
+    /// any, and holding the given transaction data accounts.
+    async fn with_config(config: Option<(Pubkey, u8)>, tx_data: Vec<(Pubkey, Account)>) -> Self {
+        Self::with_authority(Pubkey::new_unique(), Keypair::new(), config, tx_data).await
+    }
+
+    /// Starts a test ledger with the verifier at `program_id`, verifying with
+    /// `authority`, configured with `config`, if any, and holding the given
+    /// accounts.
+    async fn with_authority(
+        program_id: Pubkey,
+        authority: Keypair,
+        config: Option<(Pubkey, u8)>,
+        accounts: Vec<(Pubkey, Account)>,
+    ) -> Self {
+        let mut program_test = ProgramTest::new(
+            "eclipse_aleo_verifier",
+            program_id,
+            processor!(Processor::process),
+        );
+        aleo_proof_mock::add_to(&mut program_test);
+        let probe_id = return_data_probe::add_to(&mut program_test);
+        let reader_id = bucket_reader::add_to(&mut program_test);
+
+        // The authority pays for the verification record, and the upgrade
+        // authority for the config.
+        let upgrade_authority = Keypair::new();
+        for signer in [&authority, &upgrade_authority] {
+            program_test.add_account(
+                signer.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+
+        // The verifier runs natively, so its program data account, which
+        // only matters for its upgrade authority, is made up.
+        let program_data = [
+            &3u32.to_le_bytes()[..],
+            &0u64.to_le_bytes(),
+            &[1],
+            upgrade_authority.pubkey().as_ref(),
+        ]
+        .concat();
+        program_test.add_account(
+            instruction::find_program_data_address(&program_id),
+            Account {
+                lamports: 1_000_000_000,
+                data: program_data,
+                owner: bpf_loader_upgradeable::id(),
+                ..Account::default()
+            },
+        );
+
+        if let Some((uploader_program_id, aleo_verifier_version)) = config {
+            let (address, bump) = find_config_address(&program_id);
+            let config = VerifierConfig {
+                version: VerifierConfig::VERSION,
+                bump,
+                uploader_program_id,
+                aleo_verifier_version,
+            };
+            program_test.add_account(
+                address,
+                Account {
+                    lamports: 1_000_000_000,
+                    data: config.try_to_vec().unwrap(),
+                    owner: program_id,
+                    ..Account::default()
+                },
+            );
+        }
+
+        for (address, account) in accounts {
+            program_test.add_account(address, account);
+        }
+
+        let (banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        Self {
+            banks_client,
+            payer,
+            authority,
+            upgrade_authority,
+            recent_blockhash,
+            program_id,
+            probe_id,
+            reader_id,
+        }
+    }
+
+    fn record_address(&self, tx_id: &[u8; 32], authority: &Pubkey) -> (Pubkey, u8) {
+        find_verified_address(&self.program_id, tx_id, authority)
+    }
+
+    fn verify_instruction(
+        &self,
+        tx_id: &[u8; 32],
+        tx_data: &Pubkey,
+        record: &Pubkey,
+    ) -> Instruction {
+        let (mut instruction, _) = instruction::verify_transaction(
+            &self.program_id,
+            &self.authority.pubkey(),
+            tx_data,
+            *tx_id,
+            TX_CONTEXT,
+        );
+        instruction.accounts[1].pubkey = *record;
+
+        instruction
+    }
+
+    /// Processes `instruction` signed by `signers`, the first of which pays
+    /// the fees.
+    async fn process(
+        &mut self,
+        instruction: Instruction,
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&signers[0].pubkey()),
+            signers,
+            self.recent_blockhash,
+        );
+
+        self.banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|err| err.unwrap())
+    }
+
+    async fn verify(
+        &mut self,
+        tx_id: &[u8; 32],
+        tx_data: &Pubkey,
+    ) -> Result<Pubkey, TransactionError> {
+        let (record, _) = self.record_address(tx_id, &self.authority.pubkey());
+        let instruction = self.verify_instruction(tx_id, tx_data, &record);
+
+        let payer = Keypair::from_bytes(&self.payer.to_bytes()).unwrap();
+        let authority = Keypair::from_bytes(&self.authority.to_bytes()).unwrap();
+        if payer.pubkey() == authority.pubkey() {
+            self.process(instruction, &[&payer]).await?;
+        } else {
+            self.process(instruction, &[&payer, &authority]).await?;
+        }
+
+        Ok(record)
+    }
+
+    /// Processes a `VerifyBatch` instruction for `items`, paired with the
+    /// bucket holding their transaction, failing unless the verifier returns
+    /// `expected`. Returns the record addresses.
+    async fn verify_batch(
+        &mut self,
+        items: &[(Pubkey, [u8; 32])],
+        expected: &[BatchItemResult],
+    ) -> Result<Vec<Pubkey>, TransactionError> {
+        let items: Vec<_> = items
+            .iter()
+            .map(|(tx_data, tx_id)| {
+                let item = BatchItem {
+                    tx_id: *tx_id,
+                    context: TX_CONTEXT,
+                };
+                (*tx_data, item)
+            })
+            .collect();
+        let (instruction, records) =
+            instruction::verify_batch(&self.program_id, &self.authority.pubkey(), &items);
+        let instruction = return_data_probe::expect_return_data(
+            &self.probe_id,
+            instruction,
+            &expected.try_to_vec().unwrap(),
+        );
+
+        let payer = Keypair::from_bytes(&self.payer.to_bytes()).unwrap();
+        let authority = Keypair::from_bytes(&self.authority.to_bytes()).unwrap();
+        self.process(instruction, &[&payer, &authority]).await?;
+
+        Ok(records)
+    }
+
+    /// Reads `len` bytes at `offset` of `data_bucket` through the uploader's
+    /// CPI helper, failing unless they are `expected`.
+    async fn read_bucket(
+        &mut self,
+        data_bucket: &Pubkey,
+        offset: u32,
+        len: u32,
+        expected: &[u8],
+    ) -> Result<(), TransactionError> {
+        let instruction = return_data_probe::expect_return_data(
+            &self.probe_id,
+            bucket_reader::read(&self.reader_id, data_bucket, offset, len),
+            expected,
+        );
+
+        let payer = Keypair::from_bytes(&self.payer.to_bytes()).unwrap();
+        self.process(instruction, &[&payer]).await
+    }
+
+    async fn record(&mut self, record: &Pubkey) -> Option<AleoVerified> {
+        let account = self.banks_client.get_account(*record).await.unwrap()?;
+        assert_eq!(account.owner, self.program_id);
+
+        Some(AleoVerified::try_from_slice(&account.data).unwrap())
+    }
+
+    async fn registry(&mut self, tx_id: &[u8; 32]) -> Option<AleoTxRegistry> {
+        let (registry, _) = find_registry_address(&self.program_id, tx_id);
+        let account = self.banks_client.get_account(registry).await.unwrap()?;
+        assert_eq!(account.owner, self.program_id);
+
+        Some(AleoTxRegistry::deserialize(&mut &account.data[..]).unwrap())
+    }
+
+    /// Processes a `SetConfig` instruction signed and paid for by `signer`.
+    async fn set_config(
+        &mut self,
+        signer: &Keypair,
+        uploader_program_id: Pubkey,
+        aleo_verifier_version: u8,
+    ) -> Result<(), TransactionError> {
+        let instruction = instruction::set_config(
+            &self.program_id,
+            &signer.pubkey(),
+            uploader_program_id,
+            aleo_verifier_version,
+        );
+        self.process(instruction, &[signer]).await
+    }
+
+    async fn config(&mut self) -> Option<VerifierConfig> {
+        let (config, _) = find_config_address(&self.program_id);
+        let account = self.banks_client.get_account(config).await.unwrap()?;
+        assert_eq!(account.owner, self.program_id);
+
+        Some(VerifierConfig::try_from_slice(&account.data).unwrap())
+    }
+}
+
+/// Uploader bucket holding a serialized transaction.
+fn tx_data(content: &[u8], sealed: bool) -> (Pubkey, Account) {
+    let meta = DataBucketMeta {
+        last_updated_slot: 0,
+        authority: Some(Pubkey::new_unique()),
+        sealed,
+        len: content.len() as u32,
+        size: content.len() as u32,
+        chunks_received: 1,
+        write_seq: 1,
+        writers: Vec::new(),
+        payer: Pubkey::new_unique(),
+        created_slot: 0,
+        expiry_slots: 0,
+        merkle_root: [0; 32],
+        kind: BucketKind::Data,
+    };
+
+    let mut data = vec![0; data_bucket_len(content.len())];
+    meta.pack(&mut data);
+    data[DATA_BUCKET_CONTENT_OFFSET..][..content.len()].copy_from_slice(content);
+
+    let account = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: eclipse_uploader::id(),
+        ..Account::default()
+    };
+
+    (Pubkey::new_unique(), account)
+}
+
+fn instruction_error(code: u32) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(code))
+}
+
+#[tokio::test]
+async fn test_verify_transaction() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(1);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    let record = context.verify(&tx_id, &bucket).await.unwrap();
+
+    let (expected_record, bump) = context.record_address(&tx_id, &context.authority.pubkey());
+    assert_eq!(record, expected_record);
+    let stored = context.record(&record).await.unwrap();
+    assert_eq!(
+        stored,
+        AleoVerified {
+            version: AleoVerified::VERSION,
+            tx_id,
+            bump,
+            authority: context.authority.pubkey(),
+            slot: stored.slot,
+            timestamp: stored.timestamp,
+            context: TX_CONTEXT,
+            tx_data: bucket,
+        }
+    );
+
+    let (_, registry_bump) = find_registry_address(&context.program_id, &tx_id);
+    assert_eq!(
+        context.registry(&tx_id).await,
+        Some(AleoTxRegistry {
+            tx_id,
+            bump: registry_bump,
+            first_verifier: context.authority.pubkey(),
+            count: 1,
+            relayers: vec![context.authority.pubkey()],
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_verify_with_wrong_record_address() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(2);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    // Derived for a different authority than the signing one.
+    let (record, _) = context.record_address(&tx_id, &context.payer.pubkey());
+    let instruction = context.verify_instruction(&tx_id, &bucket, &record);
+
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let authority = Keypair::from_bytes(&context.authority.to_bytes()).unwrap();
+    assert_eq!(
+        context.process(instruction, &[&payer, &authority]).await,
+        Err(instruction_error(EclipseError::InvalidStateAccount as u32))
+    );
+    assert_eq!(context.record(&record).await, None);
+}
+
+#[tokio::test]
+async fn test_verify_twice() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(3);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    let record = context.verify(&tx_id, &bucket).await.unwrap();
+
+    // Paid by the authority this time so that the transaction differs.
+    let instruction = context.verify_instruction(&tx_id, &bucket, &record);
+    let authority = Keypair::from_bytes(&context.authority.to_bytes()).unwrap();
+    assert_eq!(
+        context.process(instruction, &[&authority]).await,
+        Err(instruction_error(SystemError::AccountAlreadyInUse as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_rejected_transaction() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(4);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+    aleo_proof_mock::reject(tx_id);
+
+    assert_eq!(
+        context.verify(&tx_id, &bucket).await,
+        Err(instruction_error(aleo_proof_mock::REJECTED))
+    );
+
+    let (record, _) = context.record_address(&tx_id, &context.authority.pubkey());
+    assert_eq!(context.record(&record).await, None);
+    assert_eq!(context.registry(&tx_id).await, None);
+}
+
+#[tokio::test]
+async fn test_records_are_per_authority() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(5);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    let first_authority = context.authority.pubkey();
+    let first_record = context.verify(&tx_id, &bucket).await.unwrap();
+
+    context.authority = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let second_record = context.verify(&tx_id, &bucket).await.unwrap();
+
+    assert_ne!(first_record, second_record);
+    assert_eq!(
+        context.record(&second_record).await.unwrap().authority,
+        context.payer.pubkey()
+    );
+
+    // Both verifications are attested in the same registry.
+    let registry = context.registry(&tx_id).await.unwrap();
+    assert_eq!(registry.first_verifier, first_authority);
+    assert_eq!(registry.count, 2);
+    assert_eq!(
+        registry.relayers,
+        vec![first_authority, context.payer.pubkey()]
+    );
+}
+
+#[tokio::test]
+async fn test_verify_unsealed_bucket() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(6);
+    let (bucket, account) = tx_data(&transaction, false);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    assert_eq!(
+        context.verify(&tx_id, &bucket).await,
+        Err(instruction_error(EclipseError::UnsealedTxData as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_verify_foreign_account() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(7);
+    let (bucket, mut account) = tx_data(&transaction, true);
+    account.owner = Pubkey::new_unique();
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    assert_eq!(
+        context.verify(&tx_id, &bucket).await,
+        Err(instruction_error(EclipseError::InvalidTxDataAccount as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_verify_other_transaction() {
+    let (_, transaction) = aleo_proof_mock::transaction(8);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    // The bucket holds a different transaction than the claimed one.
+    let (tx_id, _) = aleo_proof_mock::transaction(9);
+    assert_eq!(
+        context.verify(&tx_id, &bucket).await,
+        Err(instruction_error(EclipseError::TxIdMismatch as u32))
+    );
+
+    let (record, _) = context.record_address(&tx_id, &context.authority.pubkey());
+    assert_eq!(context.record(&record).await, None);
+}
+
+#[tokio::test]
+async fn test_verify_with_wrong_registry_address() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(10);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    let (record, _) = context.record_address(&tx_id, &context.authority.pubkey());
+    let mut instruction = context.verify_instruction(&tx_id, &bucket, &record);
+    instruction.accounts[5].pubkey = find_registry_address(&context.program_id, &[11; 32]).0;
+
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let authority = Keypair::from_bytes(&context.authority.to_bytes()).unwrap();
+    assert_eq!(
+        context.process(instruction, &[&payer, &authority]).await,
+        Err(instruction_error(
+            EclipseError::InvalidRegistryAccount as u32
+        ))
+    );
+    assert_eq!(context.record(&record).await, None);
+}
+
+#[tokio::test]
+async fn test_verify_without_context() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(12);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::new(vec![(bucket, account)]).await;
+
+    let (record, _) = context.record_address(&tx_id, &context.authority.pubkey());
+    let mut instruction = context.verify_instruction(&tx_id, &bucket, &record);
+    instruction.data = VerifierInstruction::VerifyTransaction { tx_id }
+        .try_to_vec()
+        .unwrap();
+
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let authority = Keypair::from_bytes(&context.authority.to_bytes()).unwrap();
+    context
+        .process(instruction, &[&payer, &authority])
+        .await
+        .unwrap();
+
+    let stored = context.record(&record).await.unwrap();
+    assert_eq!(stored.tx_id, tx_id);
+    assert_eq!(stored.context, AleoTxContext::default());
+    assert_eq!(stored.tx_data, bucket);
+}
+
+#[tokio::test]
+async fn test_verify_batch() {
+    let (verified, verified_transaction) = aleo_proof_mock::transaction(13);
+    let (unsealed, unsealed_transaction) = aleo_proof_mock::transaction(14);
+    let (fresh, fresh_transaction) = aleo_proof_mock::transaction(15);
+    let (mismatched, _) = aleo_proof_mock::transaction(19);
+    let (_, other_transaction) = aleo_proof_mock::transaction(20);
+    let (verified_bucket, verified_account) = tx_data(&verified_transaction, true);
+    let (unsealed_bucket, unsealed_account) = tx_data(&unsealed_transaction, false);
+    let (mismatched_bucket, mismatched_account) = tx_data(&other_transaction, true);
+    let (fresh_bucket, fresh_account) = tx_data(&fresh_transaction, true);
+    let mut context = Context::new(vec![
+        (verified_bucket, verified_account),
+        (unsealed_bucket, unsealed_account),
+        (mismatched_bucket, mismatched_account),
+        (fresh_bucket, fresh_account),
+    ])
+    .await;
+
+    let verified_record = context.verify(&verified, &verified_bucket).await.unwrap();
+
+    let records = context
+        .verify_batch(
+            &[
+                (verified_bucket, verified),
+                (unsealed_bucket, unsealed),
+                (mismatched_bucket, mismatched),
+                (fresh_bucket, fresh),
+            ],
+            &[
+                BatchItemResult::AlreadyVerified,
+                BatchItemResult::Skipped {
+                    error: EclipseError::UnsealedTxData as u32,
+                },
+                BatchItemResult::Skipped {
+                    error: EclipseError::TxIdMismatch as u32,
+                },
+                BatchItemResult::Verified,
+            ],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(records[0], verified_record);
+    assert_eq!(context.registry(&verified).await.unwrap().count, 1);
+    assert_eq!(context.record(&records[1]).await, None);
+    assert_eq!(context.registry(&unsealed).await, None);
+    assert_eq!(context.record(&records[2]).await, None);
+    assert_eq!(context.registry(&mismatched).await, None);
+
+    let stored = context.record(&records[3]).await.unwrap();
+    assert_eq!(stored.tx_id, fresh);
+    assert_eq!(stored.context, TX_CONTEXT);
+    assert_eq!(context.registry(&fresh).await.unwrap().count, 1);
+}
+
+#[tokio::test]
+async fn test_verify_batch_with_rejected_transaction() {
+    let (valid, valid_transaction) = aleo_proof_mock::transaction(16);
+    let (rejected, rejected_transaction) = aleo_proof_mock::transaction(17);
+    aleo_proof_mock::reject(rejected);
+    let (valid_bucket, valid_account) = tx_data(&valid_transaction, true);
+    let (rejected_bucket, rejected_account) = tx_data(&rejected_transaction, true);
+    let mut context = Context::new(vec![
+        (valid_bucket, valid_account),
+        (rejected_bucket, rejected_account),
+    ])
+    .await;
+
+    // The rejected proof fails the whole batch.
+    assert_eq!(
+        context
+            .verify_batch(
+                &[(valid_bucket, valid), (rejected_bucket, rejected)],
+                &[BatchItemResult::Verified, BatchItemResult::Verified],
+            )
+            .await,
+        Err(instruction_error(aleo_proof_mock::REJECTED))
+    );
+
+    let (record, _) = context.record_address(&valid, &context.authority.pubkey());
+    assert_eq!(context.record(&record).await, None);
+}
+
+#[tokio::test]
+async fn test_verify_with_prefunded_accounts() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(18);
+    let (bucket, account) = tx_data(&transaction, true);
+
+    // Anyone can fund the derived addresses before the first verification.
+    let (program_id, authority) = (Pubkey::new_unique(), Keypair::new());
+    let (record, _) = find_verified_address(&program_id, &tx_id, &authority.pubkey());
+    let (registry, _) = find_registry_address(&program_id, &tx_id);
+    let prefunded = Account {
+        lamports: 1,
+        ..Account::default()
+    };
+    let mut context = Context::with_authority(
+        program_id,
+        authority,
+        Some(CONFIG),
+        vec![
+            (bucket, account),
+            (record, prefunded.clone()),
+            (registry, prefunded),
+        ],
+    )
+    .await;
+
+    assert_eq!(context.verify(&tx_id, &bucket).await, Ok(record));
+    assert_eq!(context.record(&record).await.unwrap().tx_id, tx_id);
+    assert_eq!(context.registry(&tx_id).await.unwrap().count, 1);
+}
+
+#[tokio::test]
+async fn test_read_bucket_through_cpi() {
+    let content: Vec<u8> = (0..100).collect();
+    let (sealed, sealed_account) = tx_data(&content, true);
+    let (unsealed, unsealed_account) = tx_data(&content, false);
+    let (foreign, mut foreign_account) = tx_data(&content, true);
+    foreign_account.owner = Pubkey::new_unique();
+    let mut context = Context::new(vec![
+        (sealed, sealed_account),
+        (unsealed, unsealed_account),
+        (foreign, foreign_account),
+    ])
+    .await;
+
+    context
+        .read_bucket(&sealed, 10, 20, &content[10..30])
+        .await
+        .unwrap();
+    assert_eq!(
+        context.read_bucket(&sealed, 90, 20, &[]).await,
+        Err(instruction_error(UploaderError::OffsetOutOfBounds as u32))
+    );
+    assert_eq!(
+        context
+            .read_bucket(&unsealed, 10, 20, &content[10..30])
+            .await,
+        Err(instruction_error(UploaderError::UnsealedBucket as u32))
+    );
+    assert_eq!(
+        context
+            .read_bucket(&foreign, 10, 20, &content[10..30])
+            .await,
+        Err(instruction_error(UploaderError::WrongOwner as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_verify_with_legacy_aleo_verifier() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(21);
+    aleo_proof_mock::legacy(tx_id);
+    let (bucket, account) = tx_data(&transaction, true);
+
+    // Native verifiers returning nothing are only trusted when configured.
+    let mut context = Context::new(vec![(bucket, account.clone())]).await;
+    assert_eq!(
+        context.verify(&tx_id, &bucket).await,
+        Err(instruction_error(EclipseError::TxIdMismatch as u32))
+    );
+
+    let mut context = Context::with_config(Some((CONFIG.0, 0)), vec![(bucket, account)]).await;
+    let record = context.verify(&tx_id, &bucket).await.unwrap();
+    assert_eq!(context.record(&record).await.unwrap().tx_id, tx_id);
+}
+
+#[tokio::test]
+async fn test_verify_from_configured_uploader() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(22);
+    let (other_tx_id, other_transaction) = aleo_proof_mock::transaction(23);
+    let uploader_program_id = Pubkey::new_unique();
+    let (bucket, mut account) = tx_data(&transaction, true);
+    account.owner = uploader_program_id;
+    let (other_bucket, other_account) = tx_data(&other_transaction, true);
+    let mut context = Context::with_config(
+        Some((uploader_program_id, VerifierConfig::TX_ID_RETURNED)),
+        vec![(bucket, account), (other_bucket, other_account)],
+    )
+    .await;
+
+    context.verify(&tx_id, &bucket).await.unwrap();
+    assert_eq!(
+        context.verify(&other_tx_id, &other_bucket).await,
+        Err(instruction_error(EclipseError::InvalidTxDataAccount as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_verify_without_config() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(24);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::with_config(None, vec![(bucket, account)]).await;
+
+    assert_eq!(
+        context.verify(&tx_id, &bucket).await,
+        Err(instruction_error(EclipseError::InvalidConfigAccount as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_set_config() {
+    let (tx_id, transaction) = aleo_proof_mock::transaction(25);
+    let (bucket, account) = tx_data(&transaction, true);
+    let mut context = Context::with_config(None, vec![(bucket, account)]).await;
+    let upgrade_authority = Keypair::from_bytes(&context.upgrade_authority.to_bytes()).unwrap();
+
+    // Only the upgrade authority may set the config.
+    let authority = Keypair::from_bytes(&context.authority.to_bytes()).unwrap();
+    assert_eq!(
+        context.set_config(&authority, CONFIG.0, CONFIG.1).await,
+        Err(instruction_error(
+            EclipseError::InvalidUpgradeAuthority as u32
+        ))
+    );
+    assert_eq!(context.config().await, None);
+
+    context
+        .set_config(&upgrade_authority, Pubkey::new_unique(), 0)
+        .await
+        .unwrap();
+    context
+        .set_config(&upgrade_authority, CONFIG.0, CONFIG.1)
+        .await
+        .unwrap();
+
+    let config = context.config().await.unwrap();
+    assert_eq!(config.uploader_program_id, CONFIG.0);
+    assert_eq!(config.aleo_verifier_version, CONFIG.1);
+    assert_eq!(config.bump, find_config_address(&context.program_id).1);
+
+    context.verify(&tx_id, &bucket).await.unwrap();
+}
@@ -0,0 +1,52 @@
+//! Helpers for other programs reading data buckets through cross-program
+//! invocation. They never look into the bucket account, so that callers do
+//! not depend on the bucket layout.
+
+use {
+    crate::{error::UploaderError, instruction},
+    solana_program::{
+        account_info::AccountInfo,
+        msg,
+        program::{get_return_data, invoke},
+        program_error::ProgramError,
+    },
+};
+
+/// Reads `len` bytes at `offset` of a sealed bucket's content by invoking
+/// `ReadBucket` on the uploader program. At most `MAX_RETURN_DATA` bytes can
+/// be read at once.
+///
+/// The bucket must be owned by `uploader_program` and sealed, the latter
+/// being checked by `ReadBucket` itself. Callers are responsible for checking
+/// that `uploader_program` is the uploader program they trust.
+pub fn read_bucket<'a>(
+    uploader_program: &AccountInfo<'a>,
+    data_bucket: &AccountInfo<'a>,
+    offset: u32,
+    len: u32,
+) -> Result<Vec<u8>, ProgramError> {
+    if data_bucket.owner != uploader_program.key {
+        msg!("Data bucket must be owned by the uploader program");
+        return Err(UploaderError::WrongOwner.into());
+    }
+
+    let instruction = instruction::read_bucket(uploader_program.key, data_bucket.key, offset, len);
+    invoke(
+        &instruction,
+        &[data_bucket.clone(), uploader_program.clone()],
+    )?;
+
+    match get_return_data() {
+        Some((program_id, data))
+            if program_id == *uploader_program.key && data.len() == len as usize =>
+        {
+            Ok(data)
+        }
+        // Empty return data is not reported at all.
+        None if len == 0 => Ok(Vec::new()),
+        _ => {
+            msg!("Uploader program returned unexpected data");
+            Err(UploaderError::InvalidBucketData.into())
+        }
+    }
+}
@@ -0,0 +1,4 @@
+use {crate::process_instruction, solana_program::entrypoint};
+
+// Declare and export the program's entrypoint.
+entrypoint!(process_instruction);
@@ -0,0 +1,70 @@
+use thiserror::Error;
+
+use solana_program::program_error::ProgramError;
+
+/// Errors of the uploader program. Each variant is returned as
+/// `ProgramError::Custom` with its position as code, so new variants must
+/// only ever be appended.
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UploaderError {
+    #[error("Unknown instruction")]
+    UnknownInstruction,
+    #[error("Truncated instruction")]
+    TruncatedInstruction,
+    #[error("Offset past the declared bucket size")]
+    OffsetOutOfBounds,
+    #[error("Missing required signer")]
+    MissingSigner,
+    #[error("Bucket is frozen")]
+    FrozenBucket,
+    #[error("Bucket is not owned by the uploader program")]
+    WrongOwner,
+    #[error("Signer is not the bucket authority")]
+    WrongAuthority,
+    #[error("Bucket is sealed")]
+    SealedBucket,
+    #[error("Bucket is missing chunks")]
+    IncompleteBucket,
+    #[error("Bucket length mismatch")]
+    LengthMismatch,
+    #[error("Bucket content does not match the Merkle root")]
+    HashMismatch,
+    #[error("Bucket address does not match derived address")]
+    InvalidBucketAddress,
+    #[error("Recipient must differ from the bucket")]
+    InvalidRecipient,
+    #[error("Invalid bucket data")]
+    InvalidBucketData,
+    #[error("Unsupported bucket version")]
+    UnsupportedBucketVersion,
+    #[error("Bucket size must grow, and within the allocation limit")]
+    InvalidBucketSize,
+    #[error("Bucket changed since the expected write")]
+    WriteConflict,
+    #[error("Signer is neither the bucket authority nor a writer")]
+    NotAWriter,
+    #[error("Bucket has no room for more writers")]
+    TooManyWriters,
+    #[error("Writer is already listed")]
+    DuplicateWriter,
+    #[error("Writer is not listed")]
+    UnknownWriter,
+    #[error("Bucket has not expired")]
+    BucketNotExpired,
+    #[error("Bucket is not sealed")]
+    UnsealedBucket,
+    #[error("Read exceeds the maximum return data size")]
+    ReadTooLarge,
+    #[error("Invalid shard index")]
+    InvalidShardIndex,
+    #[error("Shard does not match the shard index")]
+    ShardMismatch,
+    #[error("Write does not cover whole chunks")]
+    UnalignedWrite,
+}
+
+impl From<UploaderError> for ProgramError {
+    fn from(e: UploaderError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
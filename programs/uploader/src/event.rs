@@ -0,0 +1,95 @@
+//! Events logged through `sol_log_data` so that indexers can follow the
+//! lifecycle of data buckets without parsing free-text logs.
+//!
+//! Each event is logged as a single Borsh-encoded field. Variants must only
+//! ever be appended to keep their encoding stable. Other programs log their
+//! own events the same way by implementing [`Event`].
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{log::sol_log_data, pubkey::Pubkey},
+};
+
+/// Prefix of the log messages carrying `sol_log_data` fields.
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// An event logged as a single Borsh-encoded `sol_log_data` field.
+pub trait Event: BorshSerialize + BorshDeserialize {
+    /// Logs the event.
+    fn emit(&self) {
+        let data = self.try_to_vec().expect("failed to serialize event");
+        sol_log_data(&[&data]);
+    }
+
+    /// Decodes an event from the data of a `sol_log_data` field.
+    fn decode(data: &[u8]) -> Option<Self> {
+        Self::try_from_slice(data).ok()
+    }
+
+    /// Decodes an event from a transaction log message, returning `None` for
+    /// messages that do not carry an event. Only messages logged while the
+    /// emitting program was executing should be passed in, as other
+    /// programs' data may happen to decode.
+    fn from_log_message(message: &str) -> Option<Self> {
+        let data = message.strip_prefix(PROGRAM_DATA_PREFIX)?;
+        Self::decode(&base64::decode(data).ok()?)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
+pub enum UploaderEvent {
+    /// A data bucket was created.
+    BucketCreated {
+        bucket: Pubkey,
+        authority: Pubkey,
+        payer: Pubkey,
+        size: u32,
+    },
+
+    /// Data was written into a data bucket.
+    ChunkWritten {
+        bucket: Pubkey,
+        offset: u32,
+        len: u32,
+        write_seq: u64,
+    },
+
+    /// A data bucket was sealed.
+    BucketFinalized {
+        bucket: Pubkey,
+        len: u32,
+        merkle_root: [u8; 32],
+    },
+
+    /// A data bucket was closed, or expired, and its lamports moved to the
+    /// recipient.
+    BucketClosed {
+        bucket: Pubkey,
+        recipient: Pubkey,
+        expired: bool,
+    },
+}
+
+impl Event for UploaderEvent {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_log_message() {
+        let event = UploaderEvent::BucketClosed {
+            bucket: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            expired: true,
+        };
+
+        let message = format!(
+            "{}{}",
+            PROGRAM_DATA_PREFIX,
+            base64::encode(event.try_to_vec().unwrap())
+        );
+        assert_eq!(UploaderEvent::from_log_message(&message), Some(event));
+        assert_eq!(UploaderEvent::from_log_message("Program log: hi"), None);
+    }
+}
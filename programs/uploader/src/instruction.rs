@@ -1,19 +1,38 @@
 use {
+    crate::{error::UploaderError, state::BucketKind},
     nom::{
         branch::alt,
         bytes::complete::{tag, take},
-        number::complete::u32,
+        combinator::map_opt,
+        multi::length_count,
+        number::complete::{u16, u32, u64, u8},
         Compare, CompareResult, Finish, IResult, InputLength,
     },
     num_enum::TryFromPrimitive,
-    solana_program::program_error::ProgramError,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_program,
+    },
 };
 
+pub use crate::state::find_bucket_address;
+
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
 enum InstructionType {
     CreateBucket,
     PutIntoBucket,
+    CloseBucket,
+    FinalizeBucket,
+    SetBucketAuthority,
+    ExtendBucket,
+    PutSegments,
+    AddWriter,
+    RemoveWriter,
+    ExpireBucket,
+    ReadBucket,
 }
 
 impl InputLength for InstructionType {
@@ -42,7 +61,9 @@ impl Compare<InstructionType> for &[u8] {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ProgramInstruction<'a> {
-    /// Create a bucket for data.
+    /// Create a bucket for data. Buckets larger than
+    /// [`crate::state::max_create_size`] are created at that size first and
+    /// grown with `ExtendBucket`.
     ///
     /// # Account references
     ///   0. `[SIGNER, WRITE]` Account used to derive and control the new data bucket.
@@ -50,22 +71,42 @@ pub enum ProgramInstruction<'a> {
     ///   2. `[WRITE]` Uninitialized data bucket account
     ///   3. `[]` System program for CPI.
     CreateBucket {
-        /// Seed data (the first bytes) to put into the bucket.
+        /// Seed data (the first bytes) to put into the bucket. Like any
+        /// write, it must cover whole chunks, see `PutIntoBucket`.
         data: &'a [u8],
 
         /// Total size of data bucket.
         size: u32,
 
+        /// Number of slots without writes after which anyone may expire the
+        /// bucket unless it is sealed. Zero means the bucket never expires.
+        expiry_slots: u64,
+
+        /// Seed of the bucket address. Either the SHA-256 of the full
+        /// content (see [`crate::state::content_seed`]) or any label chosen
+        /// by the caller.
+        seed: [u8; 32],
+
         /// Data buckets are always initialized at program-derived
-        /// addresses using the funding address, recent blockhash, and
+        /// addresses using the authority address, the `seed` and
         /// the user-passed `bump_seed`.
         bump_seed: u8,
+
+        /// What the bucket content holds.
+        kind: BucketKind,
     },
 
-    /// Put data into bucket.
+    /// Put data into bucket. The chunks of
+    /// [`crate::state::DATA_BUCKET_CHUNK_SIZE`] bytes covered by the data are
+    /// marked as received, so chunks can be put in any order and in parallel.
+    ///
+    /// The data must cover whole chunks: it must start at a chunk boundary
+    /// and end at one or at the end of the bucket content. Other writes
+    /// could never complete the chunks they cover, and are rejected with
+    /// `UnalignedWrite` rather than tracked partially.
     ///
     /// # Account references
-    ///   0. `[SIGNER, WRITE]` Account used to control the new data bucket.
+    ///   0. `[SIGNER, WRITE]` Authority or delegated writer of the data bucket.
     ///   1. `[SIGNER, WRITE]` Account that will fund the extension of data bucket.
     ///   2. `[WRITE]` Data bucket account.
     ///   3. `[]` System program for CPI.
@@ -75,6 +116,125 @@ pub enum ProgramInstruction<'a> {
 
         /// Offset
         offset: u32,
+
+        /// Reject the write unless the bucket's `write_seq` still matches.
+        expected_write_seq: Option<u64>,
+    },
+
+    /// Close bucket and reclaim its rent.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER]` Account that controls the data bucket.
+    ///   1. `[WRITE]` Data bucket account.
+    ///   2. `[WRITE]` Account that receives the bucket's lamports.
+    CloseBucket,
+
+    /// Seal bucket after verifying its content. All chunks must have been
+    /// received. Sealed buckets are immutable and store the Merkle root of
+    /// their content, against which single leaves can be verified.
+    ///
+    /// The content is verified against the Merkle root rather than a plain
+    /// hash, so that sealing hashes it only once.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER]` Account that controls the data bucket.
+    ///   1. `[WRITE]` Data bucket account.
+    FinalizeBucket {
+        /// Expected length of the bucket content.
+        size: u32,
+
+        /// Expected Merkle root of the bucket content, see
+        /// [`crate::merkle::merkle_root`].
+        merkle_root: [u8; 32],
+    },
+
+    /// Hand the bucket over to a new authority. The writers delegated by the
+    /// previous authority are removed.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER]` Account that controls the data bucket.
+    ///   1. `[WRITE]` Data bucket account.
+    SetBucketAuthority {
+        /// The new authority. `None` permanently freezes the bucket.
+        new_authority: Option<Pubkey>,
+    },
+
+    /// Grow the bucket beyond its declared size, by at most
+    /// [`crate::state::max_extend_size`], and to at most
+    /// [`crate::shard::MAX_SHARD_SIZE`] so that it can still be sealed. The
+    /// payer tops up the rent.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER]` Account that controls the data bucket.
+    ///   1. `[SIGNER, WRITE]` Account that will fund the extension of data bucket.
+    ///   2. `[WRITE]` Data bucket account.
+    ///   3. `[]` System program for CPI.
+    ExtendBucket {
+        /// New total size of data bucket.
+        new_size: u32,
+    },
+
+    /// Put several segments of data into bucket at once. Either all segments
+    /// are written or none.
+    ///
+    /// Each segment must cover whole chunks, as with `PutIntoBucket`. Chunks
+    /// of [`crate::state::DATA_BUCKET_CHUNK_SIZE`] bytes are much smaller
+    /// than a transaction, so the gaps left by failed writes can be patched
+    /// together, as many of them per transaction as fit.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER, WRITE]` Authority or delegated writer of the data bucket.
+    ///   1. `[SIGNER, WRITE]` Account that will fund the extension of data bucket.
+    ///   2. `[WRITE]` Data bucket account.
+    ///   3. `[]` System program for CPI.
+    PutSegments {
+        /// Offset and data of each segment.
+        segments: Vec<(u32, &'a [u8])>,
+
+        /// Reject the write unless the bucket's `write_seq` still matches.
+        expected_write_seq: Option<u64>,
+    },
+
+    /// Allow a delegate to put data into the bucket.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER]` Account that controls the data bucket.
+    ///   1. `[WRITE]` Data bucket account.
+    AddWriter {
+        /// The delegate to add.
+        writer: Pubkey,
+    },
+
+    /// Revoke a delegate previously allowed to put data into the bucket.
+    ///
+    /// # Account references
+    ///   0. `[SIGNER]` Account that controls the data bucket.
+    ///   1. `[WRITE]` Data bucket account.
+    RemoveWriter {
+        /// The delegate to remove.
+        writer: Pubkey,
+    },
+
+    /// Close an unsealed bucket that went without writes for longer than its
+    /// `expiry_slots`, refunding its rent to the original payer. Anyone may
+    /// expire a bucket.
+    ///
+    /// # Account references
+    ///   0. `[WRITE]` Data bucket account.
+    ///   1. `[WRITE]` Account that funded the data bucket.
+    ExpireBucket,
+
+    /// Return a slice of a sealed bucket's content through the transaction
+    /// return data, at most `MAX_RETURN_DATA` bytes. Meant for other programs
+    /// to call, see [`crate::cpi::read_bucket`].
+    ///
+    /// # Account references
+    ///   0. `[]` Data bucket account.
+    ReadBucket {
+        /// Offset into the bucket content.
+        offset: u32,
+        /// Number of bytes to read.
+        len: u32,
     },
 }
 
@@ -82,6 +242,15 @@ fn instruction_type(input: &[u8]) -> IResult<&[u8], InstructionType> {
     let (rest, kind): (&[u8], &[u8]) = alt((
         tag(InstructionType::CreateBucket),
         tag(InstructionType::PutIntoBucket),
+        tag(InstructionType::CloseBucket),
+        tag(InstructionType::FinalizeBucket),
+        tag(InstructionType::SetBucketAuthority),
+        tag(InstructionType::ExtendBucket),
+        tag(InstructionType::PutSegments),
+        tag(InstructionType::AddWriter),
+        tag(InstructionType::RemoveWriter),
+        tag(InstructionType::ExpireBucket),
+        tag(InstructionType::ReadBucket),
     ))(input)?;
 
     Ok((
@@ -95,39 +264,182 @@ fn be_u32(input: &[u8]) -> IResult<&[u8], u32> {
     u32(nom::number::Endianness::Big)(input)
 }
 
-fn create_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+fn be_u64(input: &[u8]) -> IResult<&[u8], u64> {
+    u64(nom::number::Endianness::Big)(input)
+}
+
+fn be_u16(input: &[u8]) -> IResult<&[u8], u16> {
+    u16(nom::number::Endianness::Big)(input)
+}
+
+fn parse_create_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
     let (input, size) = be_u32(input)?;
-    let (data, bump_seed) = take(1usize)(input)?;
+    let (input, expiry_slots) = be_u64(input)?;
+    let (input, seed) = take(32usize)(input)?;
+    let (input, bump_seed) = take(1usize)(input)?;
+    let (data, kind) = map_opt(u8, |kind| BucketKind::try_from(kind).ok())(input)?;
 
     Ok((
         data,
         ProgramInstruction::CreateBucket {
             data,
             size,
+            expiry_slots,
+            seed: seed
+                .try_into()
+                .expect("take returned wrong number of bytes"),
             bump_seed: bump_seed[0],
+            kind,
         },
     ))
 }
 
-fn put_into_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
-    let (data, offset) = be_u32(input)?;
+fn expected_write_seq(input: &[u8]) -> IResult<&[u8], Option<u64>> {
+    let (input, is_checked) = alt((tag(&[0][..]), tag(&[1][..])))(input)?;
+    if is_checked[0] == 0 {
+        return Ok((input, None));
+    }
 
-    Ok((data, ProgramInstruction::PutIntoBucket { data, offset }))
+    let (rest, write_seq) = be_u64(input)?;
+
+    Ok((rest, Some(write_seq)))
+}
+
+fn parse_put_into_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (input, offset) = be_u32(input)?;
+    let (data, expected_write_seq) = expected_write_seq(input)?;
+
+    Ok((
+        data,
+        ProgramInstruction::PutIntoBucket {
+            data,
+            offset,
+            expected_write_seq,
+        },
+    ))
+}
+
+fn parse_finalize_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (input, size) = be_u32(input)?;
+    let (rest, merkle_root) = take(32usize)(input)?;
+
+    Ok((
+        rest,
+        ProgramInstruction::FinalizeBucket {
+            size,
+            merkle_root: merkle_root
+                .try_into()
+                .expect("take returned wrong number of bytes"),
+        },
+    ))
+}
+
+fn parse_set_bucket_authority(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (input, has_authority) = alt((tag(&[0][..]), tag(&[1][..])))(input)?;
+    if has_authority[0] == 0 {
+        return Ok((
+            input,
+            ProgramInstruction::SetBucketAuthority {
+                new_authority: None,
+            },
+        ));
+    }
+
+    let (rest, new_authority) = take(32usize)(input)?;
+
+    Ok((
+        rest,
+        ProgramInstruction::SetBucketAuthority {
+            new_authority: Some(Pubkey::new(new_authority)),
+        },
+    ))
+}
+
+fn parse_extend_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (rest, new_size) = be_u32(input)?;
+
+    Ok((rest, ProgramInstruction::ExtendBucket { new_size }))
+}
+
+fn segment(input: &[u8]) -> IResult<&[u8], (u32, &[u8])> {
+    let (input, offset) = be_u32(input)?;
+    let (input, len) = be_u16(input)?;
+    let (rest, data) = take(len)(input)?;
+
+    Ok((rest, (offset, data)))
+}
+
+fn parse_put_segments(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (input, expected_write_seq) = expected_write_seq(input)?;
+    let (rest, segments) = length_count(u8, segment)(input)?;
+
+    Ok((
+        rest,
+        ProgramInstruction::PutSegments {
+            segments,
+            expected_write_seq,
+        },
+    ))
+}
+
+fn pubkey(input: &[u8]) -> IResult<&[u8], Pubkey> {
+    let (rest, key) = take(32usize)(input)?;
+
+    Ok((rest, Pubkey::new(key)))
+}
+
+fn parse_add_writer(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (rest, writer) = pubkey(input)?;
+
+    Ok((rest, ProgramInstruction::AddWriter { writer }))
+}
+
+fn parse_remove_writer(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (rest, writer) = pubkey(input)?;
+
+    Ok((rest, ProgramInstruction::RemoveWriter { writer }))
+}
+
+fn parse_read_bucket(input: &[u8]) -> IResult<&[u8], ProgramInstruction> {
+    let (input, offset) = be_u32(input)?;
+    let (rest, len) = be_u32(input)?;
+
+    Ok((rest, ProgramInstruction::ReadBucket { offset, len }))
 }
 
 pub fn parse_program_instruction(
     instruction_data: &[u8],
 ) -> Result<ProgramInstruction, ProgramError> {
-    let (rest, it) = instruction_type(instruction_data).finish().unwrap();
-    match it {
-        InstructionType::CreateBucket => {
-            let (_, bucket) = create_bucket(rest).unwrap();
-            Ok(bucket)
-        }
-        InstructionType::PutIntoBucket => {
-            let (_, bucket) = put_into_bucket(rest).unwrap();
-            Ok(bucket)
-        }
+    if instruction_data.is_empty() {
+        return Err(UploaderError::TruncatedInstruction.into());
+    }
+
+    let (rest, it) = instruction_type(instruction_data)
+        .finish()
+        .map_err(|_| UploaderError::UnknownInstruction)?;
+    let (_, instruction) = match it {
+        InstructionType::CreateBucket => parse_create_bucket(rest),
+        InstructionType::PutIntoBucket => parse_put_into_bucket(rest),
+        InstructionType::CloseBucket => Ok((rest, ProgramInstruction::CloseBucket)),
+        InstructionType::FinalizeBucket => parse_finalize_bucket(rest),
+        InstructionType::SetBucketAuthority => parse_set_bucket_authority(rest),
+        InstructionType::ExtendBucket => parse_extend_bucket(rest),
+        InstructionType::PutSegments => parse_put_segments(rest),
+        InstructionType::AddWriter => parse_add_writer(rest),
+        InstructionType::RemoveWriter => parse_remove_writer(rest),
+        InstructionType::ExpireBucket => Ok((rest, ProgramInstruction::ExpireBucket)),
+        InstructionType::ReadBucket => parse_read_bucket(rest),
+    }
+    .finish()
+    .map_err(|_| UploaderError::TruncatedInstruction)?;
+
+    Ok(instruction)
+}
+
+fn serialize_write_seq(expected_write_seq: &Option<u64>) -> Vec<u8> {
+    match expected_write_seq {
+        Some(write_seq) => [&[1], write_seq.to_be_bytes().as_slice()].concat(),
+        None => vec![0],
     }
 }
 
@@ -137,23 +449,318 @@ impl ProgramInstruction<'_> {
             Self::CreateBucket {
                 data,
                 size,
+                expiry_slots,
+                seed,
                 bump_seed,
+                kind,
             } => [
                 &[InstructionType::CreateBucket as u8],
                 size.to_be_bytes().as_slice(),
-                &[*bump_seed],
+                expiry_slots.to_be_bytes().as_slice(),
+                seed.as_slice(),
+                &[*bump_seed, *kind as u8],
                 data,
             ]
             .concat()
             .to_vec(),
-            Self::PutIntoBucket { data, offset } => [
+            Self::PutIntoBucket {
+                data,
+                offset,
+                expected_write_seq,
+            } => [
                 &[InstructionType::PutIntoBucket as u8],
                 offset.to_be_bytes().as_slice(),
+                serialize_write_seq(expected_write_seq).as_slice(),
                 data,
             ]
             .concat()
             .to_vec(),
+            Self::CloseBucket => vec![InstructionType::CloseBucket as u8],
+            Self::ExpireBucket => vec![InstructionType::ExpireBucket as u8],
+            Self::FinalizeBucket { size, merkle_root } => [
+                &[InstructionType::FinalizeBucket as u8],
+                size.to_be_bytes().as_slice(),
+                merkle_root.as_slice(),
+            ]
+            .concat()
+            .to_vec(),
+            Self::SetBucketAuthority { new_authority } => match new_authority {
+                Some(new_authority) => [
+                    &[InstructionType::SetBucketAuthority as u8, 1],
+                    new_authority.as_ref(),
+                ]
+                .concat()
+                .to_vec(),
+                None => vec![InstructionType::SetBucketAuthority as u8, 0],
+            },
+            Self::ExtendBucket { new_size } => [
+                &[InstructionType::ExtendBucket as u8],
+                new_size.to_be_bytes().as_slice(),
+            ]
+            .concat()
+            .to_vec(),
+            Self::PutSegments {
+                segments,
+                expected_write_seq,
+            } => {
+                let count = u8::try_from(segments.len()).expect("too many segments");
+                let mut bs = vec![InstructionType::PutSegments as u8];
+                bs.extend_from_slice(serialize_write_seq(expected_write_seq).as_slice());
+                bs.push(count);
+                for (offset, data) in segments {
+                    let len = u16::try_from(data.len()).expect("segment too long");
+                    bs.extend_from_slice(offset.to_be_bytes().as_slice());
+                    bs.extend_from_slice(len.to_be_bytes().as_slice());
+                    bs.extend_from_slice(data);
+                }
+                bs
+            }
+            Self::AddWriter { writer } => {
+                [&[InstructionType::AddWriter as u8], writer.as_ref()].concat()
+            }
+            Self::RemoveWriter { writer } => {
+                [&[InstructionType::RemoveWriter as u8], writer.as_ref()].concat()
+            }
+            Self::ReadBucket { offset, len } => [
+                &[InstructionType::ReadBucket as u8],
+                offset.to_be_bytes().as_slice(),
+                len.to_be_bytes().as_slice(),
+            ]
+            .concat(),
+        }
+    }
+}
+
+/// Creates a `CreateBucket` instruction, returning it along with the address
+/// of the new bucket derived from `authority` and `seed`.
+#[allow(clippy::too_many_arguments)]
+pub fn create_bucket(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    seed: [u8; 32],
+    seed_data: &[u8],
+    size: u32,
+    expiry_slots: u64,
+    kind: BucketKind,
+) -> (Instruction, Pubkey) {
+    let (data_bucket, bump_seed) = find_bucket_address(program_id, authority, &seed);
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(data_bucket, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: ProgramInstruction::CreateBucket {
+            data: seed_data,
+            size,
+            expiry_slots,
+            seed,
+            bump_seed,
+            kind,
+        }
+        .serialize(),
+    };
+
+    (instruction, data_bucket)
+}
+
+/// Creates a `PutIntoBucket` instruction.
+pub fn put_into_bucket(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    payer: &Pubkey,
+    data_bucket: &Pubkey,
+    offset: u32,
+    data: &[u8],
+    expected_write_seq: Option<u64>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: writer_accounts(writer, payer, data_bucket),
+        data: ProgramInstruction::PutIntoBucket {
+            data,
+            offset,
+            expected_write_seq,
         }
+        .serialize(),
+    }
+}
+
+/// Creates a `PutSegments` instruction.
+///
+/// # Panics
+///
+/// Panics if there are more than `u8::MAX` segments, or if a segment is
+/// longer than `u16::MAX` bytes.
+pub fn put_segments(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    payer: &Pubkey,
+    data_bucket: &Pubkey,
+    segments: Vec<(u32, &[u8])>,
+    expected_write_seq: Option<u64>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: writer_accounts(writer, payer, data_bucket),
+        data: ProgramInstruction::PutSegments {
+            segments,
+            expected_write_seq,
+        }
+        .serialize(),
+    }
+}
+
+fn writer_accounts(writer: &Pubkey, payer: &Pubkey, data_bucket: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*writer, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*data_bucket, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+/// Creates a `CloseBucket` instruction.
+pub fn close_bucket(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_bucket: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*data_bucket, false),
+            AccountMeta::new(*recipient, false),
+        ],
+        data: ProgramInstruction::CloseBucket.serialize(),
+    }
+}
+
+/// Creates a `FinalizeBucket` instruction.
+pub fn finalize_bucket(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_bucket: &Pubkey,
+    size: u32,
+    merkle_root: [u8; 32],
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        authority,
+        data_bucket,
+        ProgramInstruction::FinalizeBucket { size, merkle_root },
+    )
+}
+
+/// Creates a `SetBucketAuthority` instruction.
+pub fn set_bucket_authority(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_bucket: &Pubkey,
+    new_authority: Option<Pubkey>,
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        authority,
+        data_bucket,
+        ProgramInstruction::SetBucketAuthority { new_authority },
+    )
+}
+
+/// Creates an `ExtendBucket` instruction.
+pub fn extend_bucket(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    data_bucket: &Pubkey,
+    new_size: u32,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*data_bucket, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: ProgramInstruction::ExtendBucket { new_size }.serialize(),
+    }
+}
+
+/// Creates an `AddWriter` instruction.
+pub fn add_writer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_bucket: &Pubkey,
+    writer: Pubkey,
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        authority,
+        data_bucket,
+        ProgramInstruction::AddWriter { writer },
+    )
+}
+
+/// Creates a `RemoveWriter` instruction.
+pub fn remove_writer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_bucket: &Pubkey,
+    writer: Pubkey,
+) -> Instruction {
+    authority_instruction(
+        program_id,
+        authority,
+        data_bucket,
+        ProgramInstruction::RemoveWriter { writer },
+    )
+}
+
+fn authority_instruction(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_bucket: &Pubkey,
+    instruction: ProgramInstruction,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*data_bucket, false),
+        ],
+        data: instruction.serialize(),
+    }
+}
+
+/// Creates an `ExpireBucket` instruction.
+pub fn expire_bucket(program_id: &Pubkey, data_bucket: &Pubkey, payer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*data_bucket, false),
+            AccountMeta::new(*payer, false),
+        ],
+        data: ProgramInstruction::ExpireBucket.serialize(),
+    }
+}
+
+/// Creates a `ReadBucket` instruction.
+pub fn read_bucket(
+    program_id: &Pubkey,
+    data_bucket: &Pubkey,
+    offset: u32,
+    len: u32,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![AccountMeta::new_readonly(*data_bucket, false)],
+        data: ProgramInstruction::ReadBucket { offset, len }.serialize(),
     }
 }
 
@@ -167,7 +774,10 @@ mod tests {
         let orig = ProgramInstruction::CreateBucket {
             data: &[1, 2, 3, 4, 5, 6],
             size: 80,
+            expiry_slots: 1000,
+            seed: [3; 32],
             bump_seed: 42,
+            kind: BucketKind::Data,
         };
 
         let bs = orig.serialize();
@@ -181,6 +791,18 @@ mod tests {
         let orig = ProgramInstruction::PutIntoBucket {
             data: &[7, 8, 9, 10, 11, 12],
             offset: 6,
+            expected_write_seq: None,
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+
+        let orig = ProgramInstruction::PutIntoBucket {
+            data: &[7, 8, 9, 10, 11, 12],
+            offset: 6,
+            expected_write_seq: Some(3),
         };
 
         let bs = orig.serialize();
@@ -188,4 +810,192 @@ mod tests {
 
         assert_eq!(orig, new);
     }
+
+    #[test]
+    fn test_serialize_and_deserialize_close() {
+        let orig = ProgramInstruction::CloseBucket;
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_expire() {
+        let orig = ProgramInstruction::ExpireBucket;
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_finalize() {
+        let orig = ProgramInstruction::FinalizeBucket {
+            size: 1234,
+            merkle_root: [7; 32],
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_set_authority() {
+        let orig = ProgramInstruction::SetBucketAuthority {
+            new_authority: Some(Pubkey::new_unique()),
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+
+        let orig = ProgramInstruction::SetBucketAuthority {
+            new_authority: None,
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_extend() {
+        let orig = ProgramInstruction::ExtendBucket { new_size: 4096 };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_put_segments() {
+        let orig = ProgramInstruction::PutSegments {
+            segments: vec![(0, &[1, 2, 3]), (768, &[4]), (1536, &[])],
+            expected_write_seq: Some(12),
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    #[should_panic(expected = "segment too long")]
+    fn test_serialize_oversized_segment() {
+        let data = vec![0; u16::MAX as usize + 1];
+        ProgramInstruction::PutSegments {
+            segments: vec![(0, &data)],
+            expected_write_seq: None,
+        }
+        .serialize();
+    }
+
+    #[test]
+    #[should_panic(expected = "too many segments")]
+    fn test_serialize_too_many_segments() {
+        ProgramInstruction::PutSegments {
+            segments: vec![(0, &[][..]); u8::MAX as usize + 1],
+            expected_write_seq: None,
+        }
+        .serialize();
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_writers() {
+        let orig = ProgramInstruction::AddWriter {
+            writer: Pubkey::new_unique(),
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+
+        let orig = ProgramInstruction::RemoveWriter {
+            writer: Pubkey::new_unique(),
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_read() {
+        let orig = ProgramInstruction::ReadBucket {
+            offset: 768,
+            len: 1024,
+        };
+
+        let bs = orig.serialize();
+        let new = parse_program_instruction(bs.as_ref()).unwrap();
+
+        assert_eq!(orig, new);
+    }
+
+    #[test]
+    fn test_create_bucket_builder() {
+        let (program_id, authority, payer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let seed = [5; 32];
+
+        let (instruction, data_bucket) = create_bucket(
+            &program_id,
+            &authority,
+            &payer,
+            seed,
+            &[1, 2, 3],
+            10,
+            0,
+            BucketKind::Data,
+        );
+
+        let (expected_bucket, bump_seed) = find_bucket_address(&program_id, &authority, &seed);
+        assert_eq!(data_bucket, expected_bucket);
+        assert_eq!(instruction.accounts[2].pubkey, data_bucket);
+        assert_eq!(
+            parse_program_instruction(&instruction.data).unwrap(),
+            ProgramInstruction::CreateBucket {
+                data: &[1, 2, 3],
+                size: 10,
+                expiry_slots: 0,
+                seed,
+                bump_seed,
+                kind: BucketKind::Data,
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_malformed() {
+        assert_eq!(
+            parse_program_instruction(&[]),
+            Err(UploaderError::TruncatedInstruction.into())
+        );
+        assert_eq!(
+            parse_program_instruction(&[0xff]),
+            Err(UploaderError::UnknownInstruction.into())
+        );
+        assert_eq!(
+            parse_program_instruction(&[InstructionType::PutIntoBucket as u8, 0, 0]),
+            Err(UploaderError::TruncatedInstruction.into())
+        );
+        assert_eq!(
+            parse_program_instruction(&[InstructionType::SetBucketAuthority as u8, 2]),
+            Err(UploaderError::TruncatedInstruction.into())
+        );
+    }
 }
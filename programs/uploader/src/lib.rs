@@ -1,8 +1,17 @@
 #![feature(array_methods)]
 
+solana_program::declare_id!("FisxCwNwkeycmpqwLo7cJMu74W1oAm1UnYE3nKYMzmYS");
+
+pub mod cpi;
+#[cfg(not(feature = "no-entrypoint"))]
+mod entrypoint;
+pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod merkle;
 
 mod processor;
 pub use processor::process_instruction;
 
-mod state;
+pub mod shard;
+pub mod state;
@@ -0,0 +1,183 @@
+//! Merkle commitment over the content of a data bucket.
+//!
+//! Leaves are the consecutive [`LEAF_SIZE`] byte slices of the content, the
+//! last one being cut at the content length. They are coarser than the
+//! chunks tracking received content, to keep sealing cheap. A level
+//! with an odd number of nodes promotes its last node unchanged to the next
+//! level. Leaves and inner nodes are hashed with distinct prefixes so that
+//! one can never be passed off as the other.
+
+use solana_program::hash::{hashv, Hash};
+
+/// Size of the content slices the Merkle tree commits to.
+pub const LEAF_SIZE: usize = 768;
+
+/// Compute units the `sol_sha256` syscall charges per call, and per two
+/// bytes of each hashed slice.
+const SHA256_BASE_COST: u64 = 85;
+const SHA256_BYTE_COST: u64 = 1;
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Number of leaves committing to `len` bytes of content.
+pub fn leaf_count(len: usize) -> usize {
+    ((len + LEAF_SIZE - 1) / LEAF_SIZE).max(1)
+}
+
+/// Compute units spent by the syscalls hashing `len` bytes of content into
+/// its Merkle root. The instructions around them are not accounted for.
+pub fn merkle_root_compute_units(len: usize) -> u64 {
+    // Slices are charged separately, so the one-byte prefixes are free.
+    let leaves = leaf_count(len) as u64;
+    let leaf_cost = (len / LEAF_SIZE * (LEAF_SIZE / 2) + len % LEAF_SIZE / 2) as u64;
+    let node_cost = SHA256_BASE_COST + SHA256_BYTE_COST * (2 * 32 / 2);
+
+    leaves * SHA256_BASE_COST + SHA256_BYTE_COST * leaf_cost + (leaves - 1) * node_cost
+}
+
+fn leaf_hash(leaf: &[u8]) -> Hash {
+    hashv(&[LEAF_PREFIX, leaf])
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    hashv(&[NODE_PREFIX, left.as_ref(), right.as_ref()])
+}
+
+/// Computes the Merkle root over the leaves of `content`.
+///
+/// Only keeps one pending node per tree level, so it is cheap enough to run
+/// on-chain over large buckets.
+pub fn merkle_root(content: &[u8]) -> [u8; 32] {
+    if content.is_empty() {
+        return leaf_hash(&[]).to_bytes();
+    }
+
+    // Pending subtree roots with their heights, heights strictly decreasing.
+    let mut pending: Vec<(u32, Hash)> = Vec::with_capacity(32);
+    for leaf in content.chunks(LEAF_SIZE) {
+        let mut node = (0, leaf_hash(leaf));
+        while let Some((height, left)) = pending.last() {
+            if *height != node.0 {
+                break;
+            }
+            node = (height + 1, node_hash(left, &node.1));
+            pending.pop();
+        }
+        pending.push(node);
+    }
+
+    // Incomplete subtrees on the right are promoted until they meet a
+    // sibling, which amounts to folding from the right.
+    let (_, mut root) = pending.pop().expect("content has at least one leaf");
+    while let Some((_, left)) = pending.pop() {
+        root = node_hash(&left, &root);
+    }
+
+    root.to_bytes()
+}
+
+/// Builds the inclusion proof of leaf `index` of `content`, that is the
+/// sibling hashes from the leaf up to the root.
+pub fn merkle_proof(content: &[u8], index: usize) -> Option<Vec<[u8; 32]>> {
+    let mut level: Vec<Hash> = content.chunks(LEAF_SIZE).map(leaf_hash).collect();
+    if index >= level.len() {
+        return None;
+    }
+
+    let mut proof = Vec::new();
+    let mut index = index;
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            proof.push(level[sibling].to_bytes());
+        }
+
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                [node] => *node,
+                _ => unreachable!(),
+            })
+            .collect();
+        index /= 2;
+    }
+
+    Some(proof)
+}
+
+/// Verifies that `leaf` is leaf `index` out of `leaf_count` leaves committed
+/// to by `root`.
+pub fn verify_leaf(
+    root: &[u8; 32],
+    leaf_count: usize,
+    index: usize,
+    leaf: &[u8],
+    proof: &[[u8; 32]],
+) -> bool {
+    if index >= leaf_count || leaf.len() > LEAF_SIZE {
+        return false;
+    }
+
+    let mut proof = proof.iter().map(|sibling| Hash::new_from_array(*sibling));
+    let mut node = leaf_hash(leaf);
+    let mut index = index;
+    let mut width = leaf_count;
+    while width > 1 {
+        if index % 2 == 1 {
+            match proof.next() {
+                Some(left) => node = node_hash(&left, &node),
+                None => return false,
+            }
+        } else if index + 1 < width {
+            match proof.next() {
+                Some(right) => node = node_hash(&node, &right),
+                None => return false,
+            }
+        }
+
+        index /= 2;
+        width = (width + 1) / 2;
+    }
+
+    proof.next().is_none() && node.to_bytes() == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proofs_verify_against_root() {
+        for leaf_count in 1..12 {
+            let content: Vec<u8> = (0..leaf_count * LEAF_SIZE - 5).map(|i| i as u8).collect();
+            let root = merkle_root(&content);
+
+            for (index, leaf) in content.chunks(LEAF_SIZE).enumerate() {
+                let proof = merkle_proof(&content, index).unwrap();
+                assert!(verify_leaf(&root, leaf_count, index, leaf, &proof));
+                assert!(!verify_leaf(&root, leaf_count, index, &leaf[1..], &proof));
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_root_compute_units() {
+        assert_eq!(merkle_root_compute_units(0), 85);
+        assert_eq!(merkle_root_compute_units(LEAF_SIZE), 85 + 384);
+        assert_eq!(
+            merkle_root_compute_units(2 * LEAF_SIZE + 11),
+            3 * 85 + 2 * 384 + 5 + 2 * (85 + 32)
+        );
+    }
+
+    #[test]
+    fn test_proof_rejects_other_index() {
+        let content: Vec<u8> = (0..3 * LEAF_SIZE).map(|i| (i / LEAF_SIZE) as u8).collect();
+        let root = merkle_root(&content);
+        let proof = merkle_proof(&content, 0).unwrap();
+
+        assert!(!verify_leaf(&root, 3, 1, &content[..LEAF_SIZE], &proof));
+    }
+}
@@ -1,15 +1,18 @@
 use {
     crate::{
+        error::UploaderError,
+        event::{Event, UploaderEvent},
         instruction::{parse_program_instruction, ProgramInstruction},
+        merkle,
+        shard::{ShardIndex, MAX_SHARD_SIZE},
         state,
     },
     solana_program::{
         account_info::{next_account_info, AccountInfo},
         clock::Clock,
-        entrypoint,
         entrypoint::ProgramResult,
         msg,
-        program::invoke_signed,
+        program::{invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
         program_error::ProgramError,
         pubkey::Pubkey,
         rent::Rent,
@@ -18,9 +21,6 @@ use {
     },
 };
 
-// Declare and export the program's entrypoint.
-entrypoint!(process_instruction);
-
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -30,28 +30,135 @@ pub fn process_instruction(
         ProgramInstruction::CreateBucket {
             data,
             size,
+            expiry_slots,
+            seed,
+            bump_seed,
+            kind,
+        } => Processor::create_bucket(
+            program_id,
+            accounts,
+            data,
+            size as usize,
+            expiry_slots,
+            &seed,
             bump_seed,
-        } => Processor::create_bucket(program_id, accounts, data, size as usize, bump_seed),
-        ProgramInstruction::PutIntoBucket { data, offset } => {
-            Processor::put_into_bucket(program_id, accounts, data, offset as usize)
+            kind,
+        ),
+        ProgramInstruction::PutIntoBucket {
+            data,
+            offset,
+            expected_write_seq,
+        } => {
+            Processor::put_into_bucket(program_id, accounts, &[(offset, data)], expected_write_seq)
+        }
+        ProgramInstruction::PutSegments {
+            segments,
+            expected_write_seq,
+        } => Processor::put_into_bucket(program_id, accounts, &segments, expected_write_seq),
+        ProgramInstruction::CloseBucket => Processor::close_bucket(program_id, accounts),
+        ProgramInstruction::ExpireBucket => Processor::expire_bucket(program_id, accounts),
+        ProgramInstruction::FinalizeBucket { size, merkle_root } => {
+            Processor::finalize_bucket(program_id, accounts, size as usize, &merkle_root)
+        }
+        ProgramInstruction::SetBucketAuthority { new_authority } => {
+            Processor::set_bucket_authority(program_id, accounts, new_authority)
+        }
+        ProgramInstruction::ExtendBucket { new_size } => {
+            Processor::extend_bucket(program_id, accounts, new_size as usize)
+        }
+        ProgramInstruction::AddWriter { writer } => {
+            Processor::add_writer(program_id, accounts, writer)
+        }
+        ProgramInstruction::RemoveWriter { writer } => {
+            Processor::remove_writer(program_id, accounts, writer)
+        }
+        ProgramInstruction::ReadBucket { offset, len } => {
+            Processor::read_bucket(program_id, accounts, offset as usize, len as usize)
+        }
+    }
+}
+
+/// Ensures the data bucket account belongs to the uploader program.
+fn check_owner(program_id: &Pubkey, data_bucket_account: &AccountInfo) -> ProgramResult {
+    if data_bucket_account.owner != program_id {
+        msg!("Data bucket must be owned by the uploader program");
+        return Err(UploaderError::WrongOwner.into());
+    }
+
+    Ok(())
+}
+
+/// Ensures the authority account signed and controls the data bucket.
+fn check_authority(meta: &state::DataBucketMeta, authority_account: &AccountInfo) -> ProgramResult {
+    let authority_key = authority_account.signer_key().ok_or_else(|| {
+        msg!("Authority account must be a signer");
+        UploaderError::MissingSigner
+    })?;
+
+    match meta.authority {
+        None => {
+            msg!("Data bucket is frozen");
+            Err(UploaderError::FrozenBucket.into())
+        }
+        Some(authority) if authority != *authority_key => {
+            msg!("Data bucket can only be modified by its authority");
+            Err(UploaderError::WrongAuthority.into())
         }
+        Some(_) => Ok(()),
     }
 }
 
-fn adler32(data: &[u8]) -> u32 {
-    let mut hash = adler32::RollingAdler32::new();
-    hash.update_buffer(data);
-    hash.hash()
+/// Ensures the writer account signed and is either the authority or a
+/// delegated writer of the data bucket.
+fn check_writer(meta: &state::DataBucketMeta, writer_account: &AccountInfo) -> ProgramResult {
+    let writer_key = writer_account.signer_key().ok_or_else(|| {
+        msg!("Writer account must be a signer");
+        UploaderError::MissingSigner
+    })?;
+
+    match meta.authority {
+        None => {
+            msg!("Data bucket is frozen");
+            Err(UploaderError::FrozenBucket.into())
+        }
+        Some(authority) if authority == *writer_key || meta.writers.contains(writer_key) => Ok(()),
+        Some(_) => {
+            msg!("Data bucket can only be modified by its authority or writers");
+            Err(UploaderError::NotAWriter.into())
+        }
+    }
+}
+
+/// Moves all lamports of the data bucket to the recipient and wipes the
+/// bucket so that the runtime garbage collects it at the end of the
+/// transaction.
+fn drain_bucket(
+    data_bucket_account: &AccountInfo,
+    recipient_account: &AccountInfo,
+) -> ProgramResult {
+    let recipient_lamports = recipient_account
+        .lamports()
+        .checked_add(data_bucket_account.lamports())
+        .ok_or(ProgramError::InvalidArgument)?;
+    **recipient_account.lamports.borrow_mut() = recipient_lamports;
+    **data_bucket_account.lamports.borrow_mut() = 0;
+    data_bucket_account.data.borrow_mut().fill(0);
+
+    Ok(())
 }
 
 pub struct Processor;
 impl Processor {
+    #[allow(clippy::too_many_arguments)]
     fn create_bucket(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         data: &[u8],
         size: usize,
+        expiry_slots: u64,
+        seed: &[u8; 32],
         bump_seed: u8,
+        kind: state::BucketKind,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
@@ -62,25 +169,21 @@ impl Processor {
 
         let authority_key = *authority_account.signer_key().ok_or_else(|| {
             msg!("Authority account must be a signer");
-            ProgramError::MissingRequiredSignature
+            UploaderError::MissingSigner
         })?;
 
         let payer_key = *payer_account.signer_key().ok_or_else(|| {
             msg!("Payer account must be a signer");
-            ProgramError::MissingRequiredSignature
+            UploaderError::MissingSigner
         })?;
 
         // Use a derived address to ensure that an address table can never be
         // initialized more than once at the same address.
-        let adler_csum = adler32(data);
-        let adler_csum = adler_csum.to_be_bytes();
-        let adler_csum = adler_csum.as_slice();
-
         let derived_data_bucket_key = Pubkey::create_program_address(
             &[
-                b"solana-data-packer".as_ref(),
+                state::DATA_BUCKET_SEED_PREFIX,
                 authority_key.as_ref(),
-                adler_csum,
+                seed.as_ref(),
                 &[bump_seed],
             ],
             program_id,
@@ -92,17 +195,43 @@ impl Processor {
                 "Data bucket address must match derived address: {}",
                 derived_data_bucket_key
             );
-            return Err(ProgramError::InvalidArgument);
+            return Err(UploaderError::InvalidBucketAddress.into());
+        }
+
+        if data.len() > size {
+            msg!("Seed data must fit into the declared bucket size");
+            return Err(UploaderError::OffsetOutOfBounds.into());
+        }
+
+        if !state::is_aligned_write(size, 0, data.len()) {
+            msg!("Seed data must end at a chunk boundary or fill the bucket");
+            return Err(UploaderError::UnalignedWrite.into());
+        }
+
+        if !state::can_grow_to(0, size) {
+            msg!(
+                "Buckets are created with at most {} bytes and extended from there",
+                state::max_create_size(size)
+            );
+            return Err(UploaderError::InvalidBucketSize.into());
         }
 
         let current_slot = Clock::get()?.slot;
-        let data_bucket_len = 72 + size;
-        let data_bucket = state::DataBucket {
-            meta: state::DataBucketMeta {
-                last_updated_slot: current_slot,
-                authority: Some(authority_key),
-            },
-            data: data.to_vec(),
+        let data_bucket_len = state::data_bucket_len(size);
+        let mut meta = state::DataBucketMeta {
+            last_updated_slot: current_slot,
+            authority: Some(authority_key),
+            sealed: false,
+            len: data.len() as u32,
+            size: size as u32,
+            chunks_received: 0,
+            write_seq: 0,
+            writers: Vec::new(),
+            payer: payer_key,
+            created_slot: current_slot,
+            expiry_slots,
+            merkle_root: [0; 32],
+            kind,
         };
 
         let rent = Rent::default();
@@ -120,56 +249,488 @@ impl Processor {
                 program_id,
             ),
             &[
+                payer_account.clone(),
                 data_bucket_account.clone(),
-                authority_account.clone(),
                 system_program_account.clone(),
             ],
             &[&[
-                b"solana-data-packer",
+                state::DATA_BUCKET_SEED_PREFIX,
                 authority_key.as_ref(),
-                adler_csum,
+                seed.as_ref(),
                 &[bump_seed],
             ]],
         )?;
 
         // Finally store the data in the bucket.
-        data_bucket_account
-            .serialize_data(&data_bucket)
-            .map_err(|_| ProgramError::InvalidAccountData)
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let (content, bitmap) = state::split_bucket_mut(&mut bucket_data, size)?;
+        content[..data.len()].copy_from_slice(data);
+        meta.chunks_received = state::mark_received(bitmap, size, 0, data.len());
+        meta.pack(&mut bucket_data);
+
+        UploaderEvent::BucketCreated {
+            bucket: data_bucket_key,
+            authority: authority_key,
+            payer: payer_key,
+            size: size as u32,
+        }
+        .emit();
+        if !data.is_empty() {
+            UploaderEvent::ChunkWritten {
+                bucket: data_bucket_key,
+                offset: 0,
+                len: data.len() as u32,
+                write_seq: meta.write_seq,
+            }
+            .emit();
+        }
+
+        Ok(())
     }
 
     fn put_into_bucket(
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
-        data: &[u8],
-        offset: usize,
+        segments: &[(u32, &[u8])],
+        expected_write_seq: Option<u64>,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
-        let authority_account = next_account_info(account_info_iter)?;
+        let writer_account = next_account_info(account_info_iter)?;
         let _payer_account = next_account_info(account_info_iter)?;
         let data_bucket_account = next_account_info(account_info_iter)?;
         let _system_program_account = next_account_info(account_info_iter)?;
 
-        let mut data_bucket: state::DataBucket = data_bucket_account
-            .deserialize_data()
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        check_owner(program_id, data_bucket_account)?;
+
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let mut meta = state::DataBucketMeta::unpack(&bucket_data)?;
+
+        check_writer(&meta, writer_account)?;
+
+        if meta.sealed {
+            msg!("Data bucket is sealed");
+            return Err(UploaderError::SealedBucket.into());
+        }
+
+        if let Some(expected_write_seq) = expected_write_seq {
+            if meta.write_seq != expected_write_seq {
+                msg!(
+                    "Data bucket changed: expected write {}, found {}",
+                    expected_write_seq,
+                    meta.write_seq
+                );
+                return Err(UploaderError::WriteConflict.into());
+            }
+        }
+
+        // Copy the segments straight into the content region so that the cost
+        // only depends on the segment sizes. Any failing segment aborts the
+        // whole instruction, so the segments are applied atomically.
+        let size = meta.size as usize;
+        let (content, bitmap) = state::split_bucket_mut(&mut bucket_data, size)?;
+        for (offset, data) in segments {
+            let offset = *offset as usize;
+            let end = offset
+                .checked_add(data.len())
+                .filter(|end| *end <= content.len())
+                .ok_or_else(|| {
+                    msg!("Data does not fit into the data bucket");
+                    UploaderError::OffsetOutOfBounds
+                })?;
+            if !state::is_aligned_write(size, offset, end) {
+                msg!("Data must cover whole chunks of the data bucket");
+                return Err(UploaderError::UnalignedWrite.into());
+            }
+            content[offset..end].copy_from_slice(data);
+
+            meta.chunks_received += state::mark_received(bitmap, size, offset, end);
+            meta.len = meta.len.max(end as u32);
+        }
+
+        meta.record_update(Clock::get()?.slot);
+        meta.pack(&mut bucket_data);
+
+        for (offset, data) in segments {
+            UploaderEvent::ChunkWritten {
+                bucket: *data_bucket_account.key,
+                offset: *offset,
+                len: data.len() as u32,
+                write_seq: meta.write_seq,
+            }
+            .emit();
+        }
+
+        Ok(())
+    }
+
+    fn close_bucket(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let data_bucket_account = next_account_info(account_info_iter)?;
+        let recipient_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        if data_bucket_account.key == recipient_account.key {
+            msg!("Recipient must differ from the data bucket");
+            return Err(UploaderError::InvalidRecipient.into());
+        }
+
+        let meta = state::DataBucketMeta::unpack(&data_bucket_account.try_borrow_data()?)?;
+
+        check_authority(&meta, authority_account)?;
+
+        drain_bucket(data_bucket_account, recipient_account)?;
+
+        UploaderEvent::BucketClosed {
+            bucket: *data_bucket_account.key,
+            recipient: *recipient_account.key,
+            expired: false,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    fn expire_bucket(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let data_bucket_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let meta = state::DataBucketMeta::unpack(&data_bucket_account.try_borrow_data()?)?;
+
+        if meta.sealed {
+            msg!("Sealed data buckets never expire");
+            return Err(UploaderError::BucketNotExpired.into());
+        }
+
+        let current_slot = Clock::get()?.slot;
+        if !meta.is_expired(current_slot) {
+            msg!(
+                "Data bucket last updated at slot {} has not expired",
+                meta.last_updated_slot
+            );
+            return Err(UploaderError::BucketNotExpired.into());
+        }
+
+        if *payer_account.key != meta.payer {
+            msg!("Rent must be refunded to the payer {}", meta.payer);
+            return Err(UploaderError::InvalidRecipient.into());
+        }
+
+        drain_bucket(data_bucket_account, payer_account)?;
+
+        UploaderEvent::BucketClosed {
+            bucket: *data_bucket_account.key,
+            recipient: *payer_account.key,
+            expired: true,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    fn finalize_bucket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        size: usize,
+        merkle_root: &[u8; 32],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let data_bucket_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let mut meta = state::DataBucketMeta::unpack(&bucket_data)?;
+
+        check_authority(&meta, authority_account)?;
+
+        if meta.sealed {
+            msg!("Data bucket is already sealed");
+            return Err(UploaderError::SealedBucket.into());
+        }
+
+        if !meta.is_complete() {
+            msg!("Data bucket is missing chunks");
+            return Err(UploaderError::IncompleteBucket.into());
+        }
+
+        if meta.len as usize != size {
+            msg!(
+                "Data bucket length mismatch: expected {}, stored {}",
+                size,
+                meta.len
+            );
+            return Err(UploaderError::LengthMismatch.into());
+        }
+
+        // Hashing dominates the cost of sealing, so the content is hashed
+        // once, into the Merkle root that is both checked and stored.
+        let (content, _) = state::split_bucket_mut(&mut bucket_data, meta.size as usize)?;
+        if merkle::merkle_root(&content[..size]) != *merkle_root {
+            msg!("Data bucket content does not match the Merkle root");
+            return Err(UploaderError::HashMismatch.into());
+        }
+
+        if meta.kind == state::BucketKind::Index {
+            ShardIndex::unpack(&content[..size])?;
+        }
+
+        meta.sealed = true;
+        meta.merkle_root = *merkle_root;
+        meta.record_update(Clock::get()?.slot);
+        meta.pack(&mut bucket_data);
+
+        UploaderEvent::BucketFinalized {
+            bucket: *data_bucket_account.key,
+            len: meta.len,
+            merkle_root: meta.merkle_root,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    fn set_bucket_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_authority: Option<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let data_bucket_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let mut meta = state::DataBucketMeta::unpack(&bucket_data)?;
+
+        check_authority(&meta, authority_account)?;
+
+        // Writers were delegated by the previous authority.
+        meta.authority = new_authority;
+        meta.writers.clear();
+        meta.record_update(Clock::get()?.slot);
+        meta.pack(&mut bucket_data);
+
+        Ok(())
+    }
+
+    fn extend_bucket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_size: usize,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let payer_account = next_account_info(account_info_iter)?;
+        let data_bucket_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let payer_key = *payer_account.signer_key().ok_or_else(|| {
+            msg!("Payer account must be a signer");
+            UploaderError::MissingSigner
+        })?;
+
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let mut meta = state::DataBucketMeta::unpack(&bucket_data)?;
+
+        check_authority(&meta, authority_account)?;
+
+        if meta.sealed {
+            msg!("Data bucket is sealed");
+            return Err(UploaderError::SealedBucket.into());
+        }
+
+        let size = meta.size as usize;
+        if new_size <= size {
+            msg!("Data bucket size is already {}", size);
+            return Err(UploaderError::InvalidBucketSize.into());
+        }
+
+        if new_size > MAX_SHARD_SIZE {
+            msg!(
+                "Data bucket cannot be sealed beyond {} bytes",
+                MAX_SHARD_SIZE
+            );
+            return Err(UploaderError::InvalidBucketSize.into());
+        }
+
+        if !state::can_grow_to(bucket_data.len(), new_size) {
+            msg!(
+                "Data bucket can grow to at most {} bytes at once",
+                state::max_extend_size(size, new_size)
+            );
+            return Err(UploaderError::InvalidBucketSize.into());
+        }
+
+        let (_, bitmap) = state::split_bucket_mut(&mut bucket_data, size)?;
+
+        // The last chunk is cut at the bucket size. It grows with the bucket
+        // and therefore is no longer complete.
+        if size % state::DATA_BUCKET_CHUNK_SIZE != 0
+            && state::clear_received(bitmap, size / state::DATA_BUCKET_CHUNK_SIZE)
+        {
+            meta.chunks_received -= 1;
+        }
+
+        let bitmap = bitmap.to_vec();
+        drop(bucket_data);
+
+        let data_bucket_len = state::data_bucket_len(new_size);
+        data_bucket_account.realloc(data_bucket_len, false)?;
+
+        // Move the bitmap to the end of the grown content region, and the
+        // header after it. Both overwrite the old bitmap and header.
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let (content, new_bitmap) = state::split_bucket_mut(&mut bucket_data, new_size)?;
+        content[size..].fill(0);
+        new_bitmap.fill(0);
+        new_bitmap[..bitmap.len()].copy_from_slice(&bitmap);
+
+        meta.size = new_size as u32;
+        meta.record_update(Clock::get()?.slot);
+        meta.pack(&mut bucket_data);
+        drop(bucket_data);
+
+        let required_lamports = Rent::get()?
+            .minimum_balance(data_bucket_len)
+            .saturating_sub(data_bucket_account.lamports());
+
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    &payer_key,
+                    data_bucket_account.key,
+                    required_lamports,
+                ),
+                &[
+                    payer_account.clone(),
+                    data_bucket_account.clone(),
+                    system_program_account.clone(),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn add_writer(program_id: &Pubkey, accounts: &[AccountInfo], writer: Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let data_bucket_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let mut meta = state::DataBucketMeta::unpack(&bucket_data)?;
 
-        if data_bucket.meta.authority.unwrap() != *authority_account.signer_key().unwrap() {
-            return Err(ProgramError::InvalidArgument);
+        check_authority(&meta, authority_account)?;
+
+        if meta.writers.contains(&writer) {
+            msg!("Writer {} is already listed", writer);
+            return Err(UploaderError::DuplicateWriter.into());
         }
 
-        // Ensure there's enough space for new data.
-        if data_bucket.data.len() < (offset + data.len()) {
-            data_bucket.data.resize(offset + data.len(), 0);
+        if meta.writers.len() >= state::MAX_WRITERS {
+            msg!("Data bucket already has {} writers", state::MAX_WRITERS);
+            return Err(UploaderError::TooManyWriters.into());
         }
 
-        data_bucket.data[offset..].copy_from_slice(data.as_ref());
-        data_bucket.meta.last_updated_slot = Clock::get()?.slot;
+        meta.writers.push(writer);
+        meta.record_update(Clock::get()?.slot);
+        meta.pack(&mut bucket_data);
+
+        Ok(())
+    }
+
+    fn remove_writer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        writer: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_account = next_account_info(account_info_iter)?;
+        let data_bucket_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let mut bucket_data = data_bucket_account.try_borrow_mut_data()?;
+        let mut meta = state::DataBucketMeta::unpack(&bucket_data)?;
+
+        check_authority(&meta, authority_account)?;
+
+        let position = meta
+            .writers
+            .iter()
+            .position(|listed| *listed == writer)
+            .ok_or_else(|| {
+                msg!("Writer {} is not listed", writer);
+                UploaderError::UnknownWriter
+            })?;
+
+        meta.writers.remove(position);
+        meta.record_update(Clock::get()?.slot);
+        meta.pack(&mut bucket_data);
+
+        Ok(())
+    }
+
+    fn read_bucket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        offset: usize,
+        len: usize,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let data_bucket_account = next_account_info(account_info_iter)?;
+
+        check_owner(program_id, data_bucket_account)?;
+
+        let bucket_data = data_bucket_account.try_borrow_data()?;
+        let meta = state::DataBucketMeta::unpack(&bucket_data)?;
+
+        if !meta.sealed {
+            msg!("Only sealed data buckets can be read");
+            return Err(UploaderError::UnsealedBucket.into());
+        }
+
+        if len > MAX_RETURN_DATA {
+            msg!(
+                "Read of {} bytes exceeds return data limit of {}",
+                len,
+                MAX_RETURN_DATA
+            );
+            return Err(UploaderError::ReadTooLarge.into());
+        }
+
+        let end = offset.saturating_add(len);
+        if end > meta.len as usize {
+            msg!(
+                "Read past data bucket content: end {}, length {}",
+                end,
+                meta.len
+            );
+            return Err(UploaderError::OffsetOutOfBounds.into());
+        }
 
-        data_bucket_account
-            .serialize_data(&data_bucket)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let content = &bucket_data[state::DATA_BUCKET_CONTENT_OFFSET..];
+        set_return_data(&content[offset..end]);
 
         Ok(())
     }
@@ -0,0 +1,267 @@
+//! Payloads too large for a single account are split into shards, each
+//! stored in its own data bucket, and tied together by an index bucket whose
+//! content is a [`ShardIndex`].
+
+use {
+    crate::{
+        error::UploaderError,
+        merkle::LEAF_SIZE,
+        state::{BucketKind, DataBucketMeta, DATA_BUCKET_CONTENT_OFFSET},
+    },
+    arrayref::array_ref,
+    solana_program::{msg, program_error::ProgramError, pubkey::Pubkey},
+};
+
+/// Compute units that hashing may take when sealing a shard, leaving the
+/// rest of the default instruction budget of 200k to the program itself.
+pub const MAX_FINALIZE_HASH_COMPUTE_UNITS: u64 = 150_000;
+
+/// Largest shard content that can be sealed within the default compute
+/// budget, see [`MAX_FINALIZE_HASH_COMPUTE_UNITS`]. Buckets cannot be
+/// extended past it.
+pub const MAX_SHARD_SIZE: usize = 256 * LEAF_SIZE;
+
+/// A bucket holding one shard of a payload.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Shard {
+    pub bucket: Pubkey,
+    pub len: u32,
+}
+
+impl Shard {
+    const LEN: usize = 36;
+}
+
+/// Ordered list of the buckets holding the shards of a payload.
+///
+/// Encoded as the little-endian shard count followed by each shard's bucket
+/// address and little-endian length.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShardIndex {
+    pub shards: Vec<Shard>,
+}
+
+impl ShardIndex {
+    /// Reads a shard index from the content of an index bucket.
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < 4 {
+            return Err(UploaderError::InvalidShardIndex.into());
+        }
+
+        let (count, shards) = src.split_at(4);
+        let count = u32::from_le_bytes(*array_ref![count, 0, 4]) as usize;
+        if count == 0 || shards.len() != count * Shard::LEN {
+            return Err(UploaderError::InvalidShardIndex.into());
+        }
+
+        let shards = shards
+            .chunks(Shard::LEN)
+            .map(|shard| Shard {
+                bucket: Pubkey::new(&shard[..32]),
+                len: u32::from_le_bytes(*array_ref![shard, 32, 4]),
+            })
+            .collect::<Vec<_>>();
+        if shards
+            .iter()
+            .any(|shard| shard.len == 0 || shard.len as usize > MAX_SHARD_SIZE)
+        {
+            return Err(UploaderError::InvalidShardIndex.into());
+        }
+
+        Ok(Self { shards })
+    }
+
+    /// Encodes the shard index as index bucket content.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut bs = Vec::with_capacity(4 + self.shards.len() * Shard::LEN);
+        bs.extend_from_slice(&(self.shards.len() as u32).to_le_bytes());
+        for shard in &self.shards {
+            bs.extend_from_slice(shard.bucket.as_ref());
+            bs.extend_from_slice(&shard.len.to_le_bytes());
+        }
+        bs
+    }
+
+    /// Length of the whole payload.
+    pub fn total_len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len as usize).sum()
+    }
+}
+
+/// Reads the payload of a sealed bucket from its raw account data. For an
+/// index bucket, `shards` must hold the address and raw account data of each
+/// shard bucket in index order; it is ignored for a plain data bucket.
+///
+/// Callers are responsible for checking that all the accounts are owned by
+/// the uploader program.
+pub fn read_payload(
+    bucket_data: &[u8],
+    shards: &[(Pubkey, &[u8])],
+) -> Result<Vec<u8>, ProgramError> {
+    let (meta, content) = sealed_content(bucket_data)?;
+    if meta.kind == BucketKind::Data {
+        return Ok(content.to_vec());
+    }
+
+    let index = ShardIndex::unpack(content)?;
+    if index.shards.len() != shards.len() {
+        msg!(
+            "Expected {} shards, got {}",
+            index.shards.len(),
+            shards.len()
+        );
+        return Err(UploaderError::ShardMismatch.into());
+    }
+
+    let mut payload = Vec::with_capacity(index.total_len());
+    for (shard, (bucket, shard_data)) in index.shards.iter().zip(shards) {
+        let (shard_meta, shard_content) = sealed_content(shard_data)?;
+        if shard.bucket != *bucket
+            || shard_meta.kind != BucketKind::Data
+            || shard_meta.len != shard.len
+        {
+            msg!("Shard {} does not match the shard index", bucket);
+            return Err(UploaderError::ShardMismatch.into());
+        }
+
+        payload.extend_from_slice(shard_content);
+    }
+
+    Ok(payload)
+}
+
+/// Header and content written to a sealed bucket.
+fn sealed_content(bucket_data: &[u8]) -> Result<(DataBucketMeta, &[u8]), ProgramError> {
+    let meta = DataBucketMeta::unpack(bucket_data)?;
+    if !meta.sealed {
+        return Err(UploaderError::UnsealedBucket.into());
+    }
+
+    let content = bucket_data[DATA_BUCKET_CONTENT_OFFSET..]
+        .get(..meta.len as usize)
+        .ok_or(UploaderError::InvalidBucketData)?;
+
+    Ok((meta, content))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            merkle,
+            state::{data_bucket_len, max_create_size, max_extend_size, DATA_BUCKET_CHUNK_SIZE},
+        },
+        solana_program::{
+            entrypoint::MAX_PERMITTED_DATA_INCREASE, system_instruction::MAX_PERMITTED_DATA_LENGTH,
+        },
+    };
+
+    fn sealed_bucket(kind: BucketKind, content: &[u8]) -> Vec<u8> {
+        let meta = DataBucketMeta {
+            last_updated_slot: 1,
+            authority: Some(Pubkey::new_unique()),
+            sealed: true,
+            len: content.len() as u32,
+            size: content.len() as u32,
+            chunks_received: 1,
+            write_seq: 1,
+            writers: Vec::new(),
+            payer: Pubkey::new_unique(),
+            created_slot: 0,
+            expiry_slots: 0,
+            merkle_root: [0; 32],
+            kind,
+        };
+
+        let mut bucket_data = vec![0; data_bucket_len(content.len())];
+        meta.pack(&mut bucket_data);
+        bucket_data[DATA_BUCKET_CONTENT_OFFSET..][..content.len()].copy_from_slice(content);
+        bucket_data
+    }
+
+    #[test]
+    fn test_max_shard_fits_into_account() {
+        assert!(data_bucket_len(MAX_SHARD_SIZE) <= MAX_PERMITTED_DATA_LENGTH as usize);
+    }
+
+    #[test]
+    fn test_max_shard_finalizes_within_budget() {
+        assert_eq!(MAX_SHARD_SIZE % DATA_BUCKET_CHUNK_SIZE, 0);
+        assert!(
+            merkle::merkle_root_compute_units(MAX_SHARD_SIZE) <= MAX_FINALIZE_HASH_COMPUTE_UNITS
+        );
+        assert!(
+            merkle::merkle_root_compute_units(MAX_SHARD_SIZE + LEAF_SIZE)
+                > MAX_FINALIZE_HASH_COMPUTE_UNITS
+        );
+    }
+
+    #[test]
+    fn test_max_shard_reachable_by_extending() {
+        let mut size = max_create_size(MAX_SHARD_SIZE);
+        assert!(data_bucket_len(size) <= MAX_PERMITTED_DATA_INCREASE);
+
+        while size < MAX_SHARD_SIZE {
+            let next = max_extend_size(size, MAX_SHARD_SIZE);
+            assert!(next > size);
+            assert!(data_bucket_len(next) - data_bucket_len(size) <= MAX_PERMITTED_DATA_INCREASE);
+            assert!(next == MAX_SHARD_SIZE || next % DATA_BUCKET_CHUNK_SIZE == 0);
+            size = next;
+        }
+        assert_eq!(size, MAX_SHARD_SIZE);
+    }
+
+    #[test]
+    fn test_pack_and_unpack_shard_index() {
+        let index = ShardIndex {
+            shards: vec![
+                Shard {
+                    bucket: Pubkey::new_unique(),
+                    len: 10,
+                },
+                Shard {
+                    bucket: Pubkey::new_unique(),
+                    len: 3,
+                },
+            ],
+        };
+
+        assert_eq!(ShardIndex::unpack(&index.pack()).unwrap(), index);
+        assert_eq!(index.total_len(), 13);
+        assert_eq!(
+            ShardIndex::unpack(&index.pack()[1..]),
+            Err(UploaderError::InvalidShardIndex.into())
+        );
+    }
+
+    #[test]
+    fn test_read_sharded_payload() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let first_data = sealed_bucket(BucketKind::Data, &[1, 2, 3]);
+        let second_data = sealed_bucket(BucketKind::Data, &[4, 5]);
+        let index = ShardIndex {
+            shards: vec![
+                Shard {
+                    bucket: first,
+                    len: 3,
+                },
+                Shard {
+                    bucket: second,
+                    len: 2,
+                },
+            ],
+        };
+        let index_data = sealed_bucket(BucketKind::Index, &index.pack());
+
+        assert_eq!(
+            read_payload(&index_data, &[(first, &first_data), (second, &second_data)]).unwrap(),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            read_payload(&index_data, &[(second, &second_data), (first, &first_data)]),
+            Err(UploaderError::ShardMismatch.into())
+        );
+        assert_eq!(read_payload(&first_data, &[]).unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -1,21 +1,666 @@
 use {
-    serde::{Deserialize, Serialize},
-    solana_program::{clock::Slot, pubkey::Pubkey},
+    crate::{error::UploaderError, merkle},
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    num_enum::TryFromPrimitive,
+    solana_program::{
+        clock::Slot, entrypoint::MAX_PERMITTED_DATA_INCREASE, hash::hash,
+        program_error::ProgramError, pubkey::Pubkey,
+    },
+    std::ops::Range,
 };
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+/// Prefix of the seeds used to derive data bucket addresses.
+pub const DATA_BUCKET_SEED_PREFIX: &[u8] = b"solana-data-packer";
+
+/// Version of the data bucket layout written by this program.
+///
+/// Buckets created before the layout was versioned stored a bincode
+/// `DataBucket`: the last updated slot, the authority as an `Option<Pubkey>`
+/// and the content as a `Vec<u8>`. Readers outside this crate, such as the
+/// validator's native Aleo proof verifier, still deserialize it, so every
+/// bucket starts with that encoding: see [`DATA_BUCKET_CONTENT_OFFSET`]. The
+/// versioned [`DataBucketMeta`] header is stored at the end of the account.
+pub const DATA_BUCKET_VERSION: u8 = 1;
+
+/// Offset of the content region, after a prefix laid out as the bincode
+/// `DataBucket` of unversioned buckets: the little-endian last updated slot,
+/// the `Some` tag, the authority (zeroes once frozen) and the little-endian
+/// `u64` content length. The prefix mirrors the header and is only written.
+pub const DATA_BUCKET_CONTENT_OFFSET: usize = 49;
+
+/// Bucket seed derived from the full content that will be uploaded.
+pub fn content_seed(content: &[u8]) -> [u8; 32] {
+    hash(content).to_bytes()
+}
+
+/// Finds the address of the data bucket controlled by `authority` with the
+/// given `seed`.
+pub fn find_bucket_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    seed: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[DATA_BUCKET_SEED_PREFIX, authority.as_ref(), seed.as_ref()],
+        program_id,
+    )
+}
+
+/// Maximum number of delegated writers of a data bucket.
+pub const MAX_WRITERS: usize = 4;
+
+/// Granularity at which the bucket tracks received content. Chunk `i`
+/// covers the content range `[i * DATA_BUCKET_CHUNK_SIZE, (i + 1) *
+/// DATA_BUCKET_CHUNK_SIZE)`, the last chunk being cut at the bucket size.
+///
+/// Chunks are much smaller than a transaction, so that writes can be sized
+/// to fill transactions and the gaps they leave patched together.
+pub const DATA_BUCKET_CHUNK_SIZE: usize = 64;
+
+/// Number of chunks in a bucket holding `size` bytes of content.
+pub fn chunk_count(size: usize) -> usize {
+    (size + DATA_BUCKET_CHUNK_SIZE - 1) / DATA_BUCKET_CHUNK_SIZE
+}
+
+/// Size of the received-chunk bitmap of a bucket holding `size` bytes of
+/// content.
+pub fn bitmap_len(size: usize) -> usize {
+    (chunk_count(size) + 7) / 8
+}
+
+/// Size of a data bucket account able to hold `size` bytes of content.
+///
+/// The account is laid out as the legacy prefix, the content region, the
+/// received-chunk bitmap and the header, in that order.
+pub fn data_bucket_len(size: usize) -> usize {
+    DATA_BUCKET_CONTENT_OFFSET + size + bitmap_len(size) + DataBucketMeta::LEN
+}
+
+/// Largest content size, up to `target`, that a bucket can be created with.
+///
+/// Buckets are allocated through a cross-program invocation, which may not
+/// allocate more than [`MAX_PERMITTED_DATA_INCREASE`] bytes. Larger buckets
+/// are created at this size and grown with `ExtendBucket`, see
+/// [`max_extend_size`].
+pub fn max_create_size(target: usize) -> usize {
+    max_grown_size(0, 0, target)
+}
+
+/// Largest content size, up to `target`, that a bucket holding `size` bytes
+/// can be extended to by a single `ExtendBucket`, which may not grow the
+/// account by more than [`MAX_PERMITTED_DATA_INCREASE`] bytes.
+pub fn max_extend_size(size: usize, target: usize) -> usize {
+    max_grown_size(data_bucket_len(size), size, target)
+}
+
+/// Whether an account of `current_len` bytes can be grown to hold a bucket of
+/// `size` bytes in one instruction.
+pub fn can_grow_to(current_len: usize, size: usize) -> bool {
+    data_bucket_len(size).saturating_sub(current_len) <= MAX_PERMITTED_DATA_INCREASE
+}
+
+/// Grows an account of `current_len` bytes holding `size` bytes of content
+/// towards `target`. Sizes short of `target` are whole chunks, so that no
+/// chunk is cut by an intermediate size.
+fn max_grown_size(current_len: usize, size: usize, target: usize) -> usize {
+    if can_grow_to(current_len, target) {
+        return target;
+    }
+
+    let mut grown =
+        (size + MAX_PERMITTED_DATA_INCREASE) / DATA_BUCKET_CHUNK_SIZE * DATA_BUCKET_CHUNK_SIZE;
+    while grown > size && !can_grow_to(current_len, grown) {
+        grown -= DATA_BUCKET_CHUNK_SIZE;
+    }
+    grown
+}
+
+/// Content region and received-chunk bitmap of a bucket account.
+pub type DataBucketRegions<'a> = (&'a mut [u8], &'a mut [u8]);
+
+/// Splits the content region and the received-chunk bitmap out of bucket
+/// account data.
+pub fn split_bucket_mut(src: &mut [u8], size: usize) -> Result<DataBucketRegions, ProgramError> {
+    if src.len() != data_bucket_len(size) {
+        return Err(UploaderError::InvalidBucketData.into());
+    }
+
+    let (content, rest) = src[DATA_BUCKET_CONTENT_OFFSET..].split_at_mut(size);
+    let (bitmap, _header) = rest.split_at_mut(bitmap_len(size));
+    Ok((content, bitmap))
+}
+
+/// Whether the content range `[offset, end)` of a bucket holding `size`
+/// bytes covers whole chunks only. It must start at a chunk boundary and end
+/// at one or at the end of the content, which cuts the last chunk.
+///
+/// A write covering chunks only partially could never complete them, so
+/// such writes are rejected.
+pub fn is_aligned_write(size: usize, offset: usize, end: usize) -> bool {
+    offset % DATA_BUCKET_CHUNK_SIZE == 0 && (end % DATA_BUCKET_CHUNK_SIZE == 0 || end == size)
+}
+
+/// Marks every chunk entirely covered by the content range `[offset, end)`
+/// as received and returns how many of them were not received before.
+///
+/// Chunks only partially covered are left untouched, see
+/// [`is_aligned_write`].
+pub fn mark_received(bitmap: &mut [u8], size: usize, offset: usize, end: usize) -> u32 {
+    let mut newly_received = 0;
+    let first = (offset + DATA_BUCKET_CHUNK_SIZE - 1) / DATA_BUCKET_CHUNK_SIZE;
+
+    for chunk in first..chunk_count(size) {
+        let chunk_end = ((chunk + 1) * DATA_BUCKET_CHUNK_SIZE).min(size);
+        if chunk_end > end {
+            break;
+        }
+
+        let (byte, bit) = (chunk / 8, 1 << (chunk % 8));
+        if bitmap[byte] & bit == 0 {
+            bitmap[byte] |= bit;
+            newly_received += 1;
+        }
+    }
+
+    newly_received
+}
+
+/// Clears the received mark of `chunk` and returns whether it was set.
+pub fn clear_received(bitmap: &mut [u8], chunk: usize) -> bool {
+    let (byte, bit) = (chunk / 8, 1 << (chunk % 8));
+    let was_received = bitmap[byte] & bit != 0;
+    bitmap[byte] &= !bit;
+    was_received
+}
+
+/// What the content of a bucket holds.
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
+pub enum BucketKind {
+    /// Arbitrary data.
+    Data,
+    /// A [`crate::shard::ShardIndex`] listing the buckets that hold the
+    /// shards of a payload too large for a single account.
+    Index,
+}
+
+/// Owned copy of a data bucket, convenient for off-chain clients. On-chain
+/// code should use [`DataBucketMeta`] and access the content region in place.
+#[derive(Debug, PartialEq, Clone)]
 pub struct DataBucket {
     pub meta: DataBucketMeta,
     pub data: Vec<u8>,
+
+    /// Whether each chunk of the content has been received.
+    pub received: Vec<bool>,
+}
+
+impl DataBucket {
+    /// Reads a data bucket from raw account data.
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        let meta = DataBucketMeta::unpack(src)?;
+        let size = meta.size as usize;
+        if src.len() != data_bucket_len(size) || meta.len > meta.size {
+            return Err(UploaderError::InvalidBucketData.into());
+        }
+
+        let content = &src[DATA_BUCKET_CONTENT_OFFSET..][..size];
+        let bitmap = &src[DATA_BUCKET_CONTENT_OFFSET + size..][..bitmap_len(size)];
+        let received = (0..chunk_count(size))
+            .map(|chunk| bitmap[chunk / 8] & (1 << (chunk % 8)) != 0)
+            .collect();
+
+        Ok(Self {
+            data: content[..meta.len as usize].to_vec(),
+            received,
+            meta,
+        })
+    }
+
+    /// Indices of the chunks that have not been received yet.
+    pub fn missing_chunks(&self) -> Vec<usize> {
+        self.received
+            .iter()
+            .enumerate()
+            .filter(|(_, received)| !**received)
+            .map(|(chunk, _)| chunk)
+            .collect()
+    }
+
+    /// Content ranges that have not been received yet, adjacent missing
+    /// chunks being merged. Each range covers whole chunks, so it can be
+    /// written as is or split at chunk boundaries.
+    pub fn missing_ranges(&self) -> Vec<Range<usize>> {
+        let size = self.meta.size as usize;
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for chunk in self.missing_chunks() {
+            let start = chunk * DATA_BUCKET_CHUNK_SIZE;
+            let end = (start + DATA_BUCKET_CHUNK_SIZE).min(size);
+            match ranges.last_mut() {
+                Some(range) if range.end == start => range.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+        ranges
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+/// Fixed-size header stored at the end of every data bucket account, after
+/// the content region and the received-chunk bitmap.
+///
+/// The header reserves space for future fields. New fields are carved out of
+/// it and must treat all zeroes as their default so that existing buckets
+/// stay valid without bumping [`DATA_BUCKET_VERSION`].
+#[derive(Debug, PartialEq, Clone)]
 pub struct DataBucketMeta {
-    /// The slot that the bucket was last updated. To ensure consistent data
-    /// one should only consume bucket if last_updated_slot is older than
-    /// the current bank's slot.
+    /// The slot that the bucket was last updated. For buckets that are not
+    /// sealed one should only consume bucket if last_updated_slot is older
+    /// than the current bank's slot.
     pub last_updated_slot: Slot,
 
-    /// Authority address which must sign for each modification.
+    /// Authority address which must sign for each modification. A bucket
+    /// without authority is frozen and can never be modified again.
     pub authority: Option<Pubkey>,
+
+    /// Set once the bucket content has been checked against the length and
+    /// hash declared by the writer. A sealed bucket can no longer be modified
+    /// and is safe to consume regardless of `last_updated_slot`.
+    pub sealed: bool,
+
+    /// Length of the content written so far, that is the end of the
+    /// furthest write into the content region.
+    pub len: u32,
+
+    /// Declared size of the content region.
+    pub size: u32,
+
+    /// Number of chunks marked in the received-chunk bitmap.
+    pub chunks_received: u32,
+
+    /// Number of modifications since the bucket was created. Writers sharing
+    /// an authority pass the sequence they last saw to detect interleaving
+    /// writes.
+    pub write_seq: u64,
+
+    /// Delegates allowed to put data into the bucket besides the authority,
+    /// at most [`MAX_WRITERS`].
+    pub writers: Vec<Pubkey>,
+
+    /// Account that funded the bucket and gets its rent back when the
+    /// bucket expires.
+    pub payer: Pubkey,
+
+    /// The slot that the bucket was created.
+    pub created_slot: Slot,
+
+    /// Number of slots without writes after which anyone may expire an
+    /// unsealed bucket. Zero means the bucket never expires.
+    pub expiry_slots: u64,
+
+    /// Merkle root over the content chunks, see [`crate::merkle`]. Set when
+    /// the bucket is sealed, all zeroes before.
+    pub merkle_root: [u8; 32],
+
+    /// What the content holds. Index buckets are checked to hold a valid
+    /// shard index when they are sealed.
+    pub kind: BucketKind,
+}
+
+impl DataBucketMeta {
+    /// Size of the header in bytes.
+    pub const LEN: usize = 320;
+
+    /// Offset of the authority address in the legacy prefix, for clients
+    /// filtering buckets by authority. Buckets without authority hold zeroes
+    /// there.
+    pub const AUTHORITY_OFFSET: usize = 9;
+
+    /// Reads the header from the end of the bucket account data.
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < DATA_BUCKET_CONTENT_OFFSET + Self::LEN {
+            return Err(UploaderError::InvalidBucketData.into());
+        }
+
+        let src = array_ref![src, src.len() - Self::LEN, DataBucketMeta::LEN];
+        let (
+            version,
+            sealed,
+            authority_flag,
+            authority,
+            last_updated_slot,
+            len,
+            size,
+            chunks_received,
+            write_seq,
+            writers_len,
+            writers,
+            payer,
+            created_slot,
+            expiry_slots,
+            merkle_root,
+            kind,
+            _reserved,
+        ) = array_refs![
+            src,
+            1,
+            1,
+            1,
+            32,
+            8,
+            4,
+            4,
+            4,
+            8,
+            1,
+            32 * MAX_WRITERS,
+            32,
+            8,
+            8,
+            32,
+            1,
+            47
+        ];
+
+        if version[0] != DATA_BUCKET_VERSION {
+            return Err(UploaderError::UnsupportedBucketVersion.into());
+        }
+
+        let authority = match authority_flag[0] {
+            0 => None,
+            1 => Some(Pubkey::new_from_array(*authority)),
+            _ => return Err(UploaderError::InvalidBucketData.into()),
+        };
+
+        let kind = BucketKind::try_from(kind[0]).map_err(|_| UploaderError::InvalidBucketData)?;
+
+        let writers_len = writers_len[0] as usize;
+        if writers_len > MAX_WRITERS {
+            return Err(UploaderError::InvalidBucketData.into());
+        }
+        let writers = writers
+            .chunks(32)
+            .take(writers_len)
+            .map(Pubkey::new)
+            .collect();
+
+        Ok(Self {
+            last_updated_slot: Slot::from_le_bytes(*last_updated_slot),
+            authority,
+            sealed: sealed[0] != 0,
+            len: u32::from_le_bytes(*len),
+            size: u32::from_le_bytes(*size),
+            chunks_received: u32::from_le_bytes(*chunks_received),
+            write_seq: u64::from_le_bytes(*write_seq),
+            writers,
+            payer: Pubkey::new_from_array(*payer),
+            created_slot: Slot::from_le_bytes(*created_slot),
+            expiry_slots: u64::from_le_bytes(*expiry_slots),
+            merkle_root: *merkle_root,
+            kind,
+        })
+    }
+
+    /// Records a modification of the bucket at `slot`.
+    pub fn record_update(&mut self, slot: Slot) {
+        self.last_updated_slot = slot;
+        self.write_seq += 1;
+    }
+
+    /// Whether the bucket went without writes for long enough to expire at
+    /// `current_slot`.
+    pub fn is_expired(&self, current_slot: Slot) -> bool {
+        self.expiry_slots != 0
+            && current_slot.saturating_sub(self.last_updated_slot) >= self.expiry_slots
+    }
+
+    /// Whether every chunk of the content has been received.
+    pub fn is_complete(&self) -> bool {
+        self.chunks_received as usize == chunk_count(self.size as usize)
+    }
+
+    /// Verifies that `leaf` is Merkle leaf `index` of a sealed bucket's
+    /// content against the stored Merkle root, without hashing the whole
+    /// content.
+    pub fn verify_leaf(&self, index: usize, leaf: &[u8], proof: &[[u8; 32]]) -> bool {
+        self.sealed
+            && merkle::verify_leaf(
+                &self.merkle_root,
+                merkle::leaf_count(self.len as usize),
+                index,
+                leaf,
+                proof,
+            )
+    }
+
+    /// Writes the header to the end of the bucket account data, and mirrors
+    /// it into the legacy prefix at the start.
+    pub fn pack(&self, dst: &mut [u8]) {
+        let prefix = array_mut_ref![dst, 0, DATA_BUCKET_CONTENT_OFFSET];
+        let (legacy_slot, legacy_tag, legacy_authority, legacy_len) =
+            mut_array_refs![prefix, 8, 1, 32, 8];
+        *legacy_slot = self.last_updated_slot.to_le_bytes();
+        legacy_tag[0] = 1;
+        match self.authority {
+            Some(key) => legacy_authority.copy_from_slice(key.as_ref()),
+            None => legacy_authority.fill(0),
+        }
+        *legacy_len = u64::from(self.len).to_le_bytes();
+
+        let offset = dst.len() - Self::LEN;
+        let dst = array_mut_ref![dst, offset, DataBucketMeta::LEN];
+        let (
+            version,
+            sealed,
+            authority_flag,
+            authority,
+            last_updated_slot,
+            len,
+            size,
+            chunks_received,
+            write_seq,
+            writers_len,
+            writers,
+            payer,
+            created_slot,
+            expiry_slots,
+            merkle_root,
+            kind,
+            reserved,
+        ) = mut_array_refs![
+            dst,
+            1,
+            1,
+            1,
+            32,
+            8,
+            4,
+            4,
+            4,
+            8,
+            1,
+            32 * MAX_WRITERS,
+            32,
+            8,
+            8,
+            32,
+            1,
+            47
+        ];
+
+        version[0] = DATA_BUCKET_VERSION;
+        sealed[0] = self.sealed as u8;
+        match self.authority {
+            Some(key) => {
+                authority_flag[0] = 1;
+                authority.copy_from_slice(key.as_ref());
+            }
+            None => {
+                authority_flag[0] = 0;
+                authority.fill(0);
+            }
+        }
+        *last_updated_slot = self.last_updated_slot.to_le_bytes();
+        *len = self.len.to_le_bytes();
+        *size = self.size.to_le_bytes();
+        *chunks_received = self.chunks_received.to_le_bytes();
+        *write_seq = self.write_seq.to_le_bytes();
+        writers_len[0] = self.writers.len() as u8;
+        writers.fill(0);
+        for (slot, writer) in writers.chunks_mut(32).zip(&self.writers) {
+            slot.copy_from_slice(writer.as_ref());
+        }
+        payer.copy_from_slice(self.payer.as_ref());
+        *created_slot = self.created_slot.to_le_bytes();
+        *expiry_slots = self.expiry_slots.to_le_bytes();
+        *merkle_root = self.merkle_root;
+        kind[0] = self.kind as u8;
+        reserved.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_and_unpack_bucket() {
+        let meta = DataBucketMeta {
+            last_updated_slot: 42,
+            authority: Some(Pubkey::new_unique()),
+            sealed: true,
+            len: 3,
+            size: 5,
+            chunks_received: 0,
+            write_seq: 7,
+            writers: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            payer: Pubkey::new_unique(),
+            created_slot: 40,
+            expiry_slots: 100,
+            merkle_root: [9; 32],
+            kind: BucketKind::Index,
+        };
+
+        let mut bucket_data = vec![0; data_bucket_len(5)];
+        meta.pack(&mut bucket_data);
+        bucket_data[DATA_BUCKET_CONTENT_OFFSET..][..3].copy_from_slice(&[1, 2, 3]);
+
+        let authority = &bucket_data[DataBucketMeta::AUTHORITY_OFFSET..][..32];
+        assert_eq!(authority, meta.authority.unwrap().as_ref());
+
+        let bucket = DataBucket::unpack(&bucket_data).unwrap();
+        assert_eq!(bucket.meta, meta);
+        assert_eq!(bucket.data, vec![1, 2, 3]);
+        assert_eq!(bucket.missing_chunks(), vec![0]);
+    }
+
+    #[test]
+    fn test_native_verifier_offsets() {
+        let meta = DataBucketMeta {
+            last_updated_slot: 42,
+            authority: None,
+            sealed: true,
+            len: 0x0102_0304,
+            size: 5,
+            chunks_received: 0,
+            write_seq: 0,
+            writers: vec![],
+            payer: Pubkey::new_unique(),
+            created_slot: 40,
+            expiry_slots: 100,
+            merkle_root: [0; 32],
+            kind: BucketKind::Data,
+        };
+
+        // The bincode `DataBucket` the native Aleo proof verifier reads.
+        let mut bucket_data = vec![0; data_bucket_len(5)];
+        meta.pack(&mut bucket_data);
+        assert_eq!(bucket_data[..8], 42u64.to_le_bytes());
+        assert_eq!(bucket_data[8], 1);
+        assert_eq!(bucket_data[9..41], [0; 32]);
+        assert_eq!(bucket_data[41..49], 0x0102_0304u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_mark_received() {
+        let size = 3 * DATA_BUCKET_CHUNK_SIZE + 10;
+        let mut bitmap = vec![0; bitmap_len(size)];
+
+        // Partially covered chunks are not marked.
+        assert_eq!(
+            mark_received(&mut bitmap, size, 1, DATA_BUCKET_CHUNK_SIZE),
+            0
+        );
+
+        assert_eq!(
+            mark_received(&mut bitmap, size, DATA_BUCKET_CHUNK_SIZE, size),
+            3
+        );
+        assert_eq!(
+            mark_received(&mut bitmap, size, 0, DATA_BUCKET_CHUNK_SIZE),
+            1
+        );
+        assert_eq!(mark_received(&mut bitmap, size, 0, size), 0);
+        assert_eq!(bitmap, vec![0b1111]);
+    }
+
+    #[test]
+    fn test_missing_ranges() {
+        let size = 5 * DATA_BUCKET_CHUNK_SIZE + 10;
+        let mut bucket_data = vec![0; data_bucket_len(size)];
+        let meta = DataBucketMeta {
+            last_updated_slot: 0,
+            authority: None,
+            sealed: false,
+            len: size as u32,
+            size: size as u32,
+            chunks_received: 2,
+            write_seq: 2,
+            writers: vec![],
+            payer: Pubkey::new_unique(),
+            created_slot: 0,
+            expiry_slots: 0,
+            merkle_root: [0; 32],
+            kind: BucketKind::Data,
+        };
+        meta.pack(&mut bucket_data);
+        let (_, bitmap) = split_bucket_mut(&mut bucket_data, size).unwrap();
+        mark_received(bitmap, size, 0, DATA_BUCKET_CHUNK_SIZE);
+        mark_received(
+            bitmap,
+            size,
+            3 * DATA_BUCKET_CHUNK_SIZE,
+            4 * DATA_BUCKET_CHUNK_SIZE,
+        );
+
+        let bucket = DataBucket::unpack(&bucket_data).unwrap();
+        assert_eq!(
+            bucket.missing_ranges(),
+            vec![
+                DATA_BUCKET_CHUNK_SIZE..3 * DATA_BUCKET_CHUNK_SIZE,
+                4 * DATA_BUCKET_CHUNK_SIZE..size
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aligned_write() {
+        let size = 3 * DATA_BUCKET_CHUNK_SIZE + 10;
+
+        assert!(is_aligned_write(size, 0, DATA_BUCKET_CHUNK_SIZE));
+        assert!(is_aligned_write(size, DATA_BUCKET_CHUNK_SIZE, size));
+        assert!(is_aligned_write(size, 0, 0));
+        assert!(!is_aligned_write(size, 1, DATA_BUCKET_CHUNK_SIZE));
+        assert!(!is_aligned_write(size, 0, DATA_BUCKET_CHUNK_SIZE - 1));
+        assert!(!is_aligned_write(size, 0, size - 1));
+    }
+
+    #[test]
+    fn test_unpack_unknown_version() {
+        let bucket_data = vec![0; data_bucket_len(5)];
+
+        assert_eq!(
+            DataBucketMeta::unpack(&bucket_data),
+            Err(UploaderError::UnsupportedBucketVersion.into())
+        );
+    }
 }
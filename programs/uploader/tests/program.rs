@@ -0,0 +1,959 @@
+use {
+    eclipse_uploader::{
+        error::UploaderError,
+        instruction, merkle, process_instruction,
+        shard::MAX_SHARD_SIZE,
+        state::{max_create_size, max_extend_size, BucketKind, DataBucket, DATA_BUCKET_CHUNK_SIZE},
+    },
+    solana_program::{
+        instruction::InstructionError, pubkey::Pubkey, system_instruction::SystemError,
+    },
+    solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext},
+    solana_sdk::{
+        hash::Hash,
+        instruction::Instruction,
+        signature::{read_keypair_file, Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+const SEED: [u8; 32] = [7; 32];
+const CHUNK: u32 = DATA_BUCKET_CHUNK_SIZE as u32;
+const SIZE: u32 = 2 * CHUNK + 10;
+
+struct Context {
+    banks_client: BanksClient,
+    payer: Keypair,
+    authority: Keypair,
+    recent_blockhash: Hash,
+    program_id: Pubkey,
+    test_context: ProgramTestContext,
+}
+
+impl Context {
+    async fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "eclipse_uploader",
+            program_id,
+            processor!(process_instruction),
+        );
+        let test_context = program_test.start_with_context().await;
+
+        Self {
+            banks_client: test_context.banks_client.clone(),
+            payer: Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap(),
+            authority: Keypair::new(),
+            recent_blockhash: test_context.last_blockhash,
+            program_id,
+            test_context,
+        }
+    }
+
+    async fn process(
+        &mut self,
+        instruction: Instruction,
+        signer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        self.process_with(instruction, &[signer]).await
+    }
+
+    /// Processes `instruction` signed by the payer and `signers`.
+    async fn process_with(
+        &mut self,
+        instruction: Instruction,
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let mut keypairs = vec![&self.payer];
+        keypairs.extend(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.payer.pubkey()),
+            &keypairs,
+            self.recent_blockhash,
+        );
+
+        self.banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|err| err.unwrap())
+    }
+
+    async fn create_bucket(&mut self, seed_data: &[u8]) -> Result<Pubkey, TransactionError> {
+        self.create_expiring_bucket(seed_data, 0).await
+    }
+
+    async fn create_expiring_bucket(
+        &mut self,
+        seed_data: &[u8],
+        expiry_slots: u64,
+    ) -> Result<Pubkey, TransactionError> {
+        let (instruction, data_bucket) = instruction::create_bucket(
+            &self.program_id,
+            &self.authority.pubkey(),
+            &self.payer.pubkey(),
+            SEED,
+            seed_data,
+            SIZE,
+            expiry_slots,
+            BucketKind::Data,
+        );
+
+        let authority = self.authority();
+        self.process(instruction, &authority).await?;
+
+        Ok(data_bucket)
+    }
+
+    async fn put_into_bucket(
+        &mut self,
+        writer: &Keypair,
+        data_bucket: &Pubkey,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), TransactionError> {
+        let instruction = instruction::put_into_bucket(
+            &self.program_id,
+            &writer.pubkey(),
+            &self.payer.pubkey(),
+            data_bucket,
+            offset,
+            data,
+            None,
+        );
+
+        self.process(instruction, writer).await
+    }
+
+    /// Creates a bucket holding `content` in full, ready to be finalized.
+    async fn create_filled_bucket(&mut self, content: &[u8]) -> Pubkey {
+        let data_bucket = self
+            .create_bucket(&content[..DATA_BUCKET_CHUNK_SIZE])
+            .await
+            .unwrap();
+
+        let authority = self.authority();
+        for (i, chunk) in content.chunks(DATA_BUCKET_CHUNK_SIZE).enumerate() {
+            let offset = (i * DATA_BUCKET_CHUNK_SIZE) as u32;
+            self.put_into_bucket(&authority, &data_bucket, offset, chunk)
+                .await
+                .unwrap();
+        }
+
+        data_bucket
+    }
+
+    async fn finalize_bucket(
+        &mut self,
+        data_bucket: &Pubkey,
+        content: &[u8],
+    ) -> Result<(), TransactionError> {
+        let instruction = instruction::finalize_bucket(
+            &self.program_id,
+            &self.authority.pubkey(),
+            data_bucket,
+            content.len() as u32,
+            merkle::merkle_root(content),
+        );
+
+        let authority = self.authority();
+        self.process(instruction, &authority).await
+    }
+
+    /// Fetches a new blockhash, as the test bank only takes so many
+    /// transactions signed over the same one.
+    async fn refresh_blockhash(&mut self) {
+        self.recent_blockhash = self.banks_client.get_latest_blockhash().await.unwrap();
+    }
+
+    async fn bucket(&mut self, data_bucket: &Pubkey) -> DataBucket {
+        let account = self
+            .banks_client
+            .get_account(*data_bucket)
+            .await
+            .unwrap()
+            .expect("data bucket does not exist");
+        assert_eq!(account.owner, self.program_id);
+
+        DataBucket::unpack(&account.data).unwrap()
+    }
+
+    /// Copy of the authority keypair, to sign while `self` is borrowed.
+    fn authority(&self) -> Keypair {
+        Keypair::from_bytes(&self.authority.to_bytes()).unwrap()
+    }
+}
+
+/// Content filling a bucket of [`SIZE`] bytes.
+fn content() -> Vec<u8> {
+    (0..SIZE).map(|i| i as u8).collect()
+}
+
+/// A whole chunk of `byte`s, to be written at a chunk boundary.
+fn chunk(byte: u8) -> Vec<u8> {
+    vec![byte; DATA_BUCKET_CHUNK_SIZE]
+}
+
+fn uploader_error(error: UploaderError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[test]
+fn test_program_id_matches_keypair() {
+    let keypair = read_keypair_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/uploader-keypair.json"
+    ))
+    .unwrap();
+    assert_eq!(keypair.pubkey(), eclipse_uploader::id());
+}
+
+#[tokio::test]
+async fn test_create_bucket() {
+    let mut context = Context::new().await;
+
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+    assert_eq!(bucket.meta.size, SIZE);
+    assert_eq!(bucket.meta.authority, Some(context.authority.pubkey()));
+    assert_eq!(bucket.meta.payer, context.payer.pubkey());
+    assert!(!bucket.meta.sealed);
+}
+
+#[tokio::test]
+async fn test_put_into_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let authority = context.authority();
+    context
+        .put_into_bucket(&authority, &data_bucket, CHUNK, &chunk(4))
+        .await
+        .unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, [chunk(1), chunk(4)].concat());
+    assert_eq!(bucket.meta.write_seq, 1);
+}
+
+#[tokio::test]
+async fn test_overwrite_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let authority = context.authority();
+    context
+        .put_into_bucket(&authority, &data_bucket, 0, &chunk(9))
+        .await
+        .unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(9));
+}
+
+#[tokio::test]
+async fn test_put_out_of_range() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let authority = context.authority();
+    assert_eq!(
+        context
+            .put_into_bucket(&authority, &data_bucket, SIZE - 1, &[4, 5])
+            .await,
+        Err(uploader_error(UploaderError::OffsetOutOfBounds))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_put_unaligned() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    // Neither write could ever complete the chunks it covers.
+    let authority = context.authority();
+    assert_eq!(
+        context
+            .put_into_bucket(&authority, &data_bucket, 1, &chunk(4))
+            .await,
+        Err(uploader_error(UploaderError::UnalignedWrite))
+    );
+    assert_eq!(
+        context
+            .put_into_bucket(&authority, &data_bucket, CHUNK, &[4, 5])
+            .await,
+        Err(uploader_error(UploaderError::UnalignedWrite))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_create_with_unaligned_seed_data() {
+    let mut context = Context::new().await;
+
+    assert_eq!(
+        context.create_bucket(&[1, 2, 3]).await,
+        Err(uploader_error(UploaderError::UnalignedWrite))
+    );
+}
+
+#[tokio::test]
+async fn test_put_with_wrong_authority() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let intruder = Keypair::new();
+    assert_eq!(
+        context
+            .put_into_bucket(&intruder, &data_bucket, CHUNK, &chunk(4))
+            .await,
+        Err(uploader_error(UploaderError::NotAWriter))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_create_at_wrong_address() {
+    let mut context = Context::new().await;
+
+    let (mut instruction, _) = instruction::create_bucket(
+        &context.program_id,
+        &context.authority.pubkey(),
+        &context.payer.pubkey(),
+        SEED,
+        &chunk(1),
+        SIZE,
+        0,
+        BucketKind::Data,
+    );
+    let wrong_bucket = Pubkey::new_unique();
+    instruction.accounts[2].pubkey = wrong_bucket;
+
+    let authority = context.authority();
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::InvalidBucketAddress))
+    );
+    assert_eq!(
+        context
+            .banks_client
+            .get_account(wrong_bucket)
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_create_bucket_collision() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    // Same address seed with different content.
+    assert_eq!(
+        context.create_bucket(&chunk(4)).await,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(SystemError::AccountAlreadyInUse as u32)
+        ))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_create_oversized_bucket() {
+    let mut context = Context::new().await;
+
+    // Too large to be allocated by the program in one go.
+    let size = max_create_size(u32::MAX as usize) + DATA_BUCKET_CHUNK_SIZE;
+    let (instruction, data_bucket) = instruction::create_bucket(
+        &context.program_id,
+        &context.authority.pubkey(),
+        &context.payer.pubkey(),
+        SEED,
+        &chunk(1),
+        size as u32,
+        0,
+        BucketKind::Data,
+    );
+
+    let authority = context.authority();
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::InvalidBucketSize))
+    );
+    assert_eq!(
+        context.banks_client.get_account(data_bucket).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_put_segments() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let instruction = instruction::put_segments(
+        &context.program_id,
+        &context.authority.pubkey(),
+        &context.payer.pubkey(),
+        &data_bucket,
+        vec![(2 * CHUNK, &[6; 10][..]), (CHUNK, &chunk(4)[..])],
+        None,
+    );
+    let authority = context.authority();
+    context.process(instruction, &authority).await.unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, [chunk(1), chunk(4), vec![6; 10]].concat());
+    assert_eq!(bucket.meta.write_seq, 1);
+    assert!(bucket.missing_ranges().is_empty());
+    assert!(bucket.meta.is_complete());
+}
+
+#[tokio::test]
+async fn test_put_segments_out_of_range() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    // The segments are applied all or nothing.
+    let instruction = instruction::put_segments(
+        &context.program_id,
+        &context.authority.pubkey(),
+        &context.payer.pubkey(),
+        &data_bucket,
+        vec![(CHUNK, &chunk(4)[..]), (SIZE - 1, &[6, 7][..])],
+        None,
+    );
+    let authority = context.authority();
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::OffsetOutOfBounds))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_put_with_expected_write_seq() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let put = |context: &Context, data: &[u8], expected_write_seq| {
+        instruction::put_into_bucket(
+            &context.program_id,
+            &context.authority.pubkey(),
+            &context.payer.pubkey(),
+            &data_bucket,
+            CHUNK,
+            data,
+            Some(expected_write_seq),
+        )
+    };
+
+    let authority = context.authority();
+    let instruction = put(&context, &chunk(4), 0);
+    context.process(instruction, &authority).await.unwrap();
+
+    // Another writer wrote since write 0 was seen.
+    let instruction = put(&context, &chunk(8), 0);
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::WriteConflict))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, [chunk(1), chunk(4)].concat());
+    assert_eq!(bucket.meta.write_seq, 1);
+}
+
+#[tokio::test]
+async fn test_add_and_remove_writer() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let writer = Keypair::new();
+    let authority = context.authority();
+    let instruction = instruction::add_writer(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        writer.pubkey(),
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    context
+        .put_into_bucket(&writer, &data_bucket, CHUNK, &chunk(4))
+        .await
+        .unwrap();
+
+    let instruction = instruction::remove_writer(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        writer.pubkey(),
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    assert_eq!(
+        context
+            .put_into_bucket(&writer, &data_bucket, CHUNK, &chunk(6))
+            .await,
+        Err(uploader_error(UploaderError::NotAWriter))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, [chunk(1), chunk(4)].concat());
+    assert!(bucket.meta.writers.is_empty());
+}
+
+#[tokio::test]
+async fn test_add_writer_without_authority() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let intruder = Keypair::new();
+    let instruction = instruction::add_writer(
+        &context.program_id,
+        &intruder.pubkey(),
+        &data_bucket,
+        intruder.pubkey(),
+    );
+    assert_eq!(
+        context.process(instruction, &intruder).await,
+        Err(uploader_error(UploaderError::WrongAuthority))
+    );
+}
+
+#[tokio::test]
+async fn test_set_bucket_authority() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let new_authority = Keypair::new();
+    let authority = context.authority();
+    let instruction = instruction::set_bucket_authority(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        Some(new_authority.pubkey()),
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    assert_eq!(
+        context
+            .put_into_bucket(&authority, &data_bucket, CHUNK, &chunk(4))
+            .await,
+        Err(uploader_error(UploaderError::NotAWriter))
+    );
+    context
+        .put_into_bucket(&new_authority, &data_bucket, CHUNK, &chunk(4))
+        .await
+        .unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.meta.authority, Some(new_authority.pubkey()));
+    assert_eq!(bucket.data, [chunk(1), chunk(4)].concat());
+}
+
+#[tokio::test]
+async fn test_set_bucket_authority_removes_writers() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let writer = Keypair::new();
+    let authority = context.authority();
+    let instruction = instruction::add_writer(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        writer.pubkey(),
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    let new_authority = Keypair::new();
+    let instruction = instruction::set_bucket_authority(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        Some(new_authority.pubkey()),
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    // The writer was delegated by the former authority.
+    assert_eq!(
+        context
+            .put_into_bucket(&writer, &data_bucket, CHUNK, &chunk(4))
+            .await,
+        Err(uploader_error(UploaderError::NotAWriter))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert!(bucket.meta.writers.is_empty());
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_freeze_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let authority = context.authority();
+    let instruction = instruction::set_bucket_authority(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        None,
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    assert_eq!(
+        context
+            .put_into_bucket(&authority, &data_bucket, CHUNK, &chunk(4))
+            .await,
+        Err(uploader_error(UploaderError::FrozenBucket))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.meta.authority, None);
+}
+
+#[tokio::test]
+async fn test_extend_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let authority = context.authority();
+    let instruction = instruction::extend_bucket(
+        &context.program_id,
+        &authority.pubkey(),
+        &context.payer.pubkey(),
+        &data_bucket,
+        2 * SIZE,
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    // The last chunk is cut at the new size.
+    let offset = 2 * SIZE / CHUNK * CHUNK;
+    context
+        .put_into_bucket(&authority, &data_bucket, offset, &[4; 20])
+        .await
+        .unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.meta.size, 2 * SIZE);
+    assert_eq!(bucket.data.len(), 2 * SIZE as usize);
+    assert_eq!(bucket.data[..DATA_BUCKET_CHUNK_SIZE], chunk(1));
+    assert_eq!(bucket.data[DATA_BUCKET_CHUNK_SIZE..][..10], [0; 10]);
+}
+
+#[tokio::test]
+async fn test_extend_beyond_allocation_limit() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    // Growing the account past the limit of a single instruction.
+    let new_size = max_extend_size(SIZE as usize, u32::MAX as usize) + DATA_BUCKET_CHUNK_SIZE;
+    let authority = context.authority();
+    let instruction = instruction::extend_bucket(
+        &context.program_id,
+        &authority.pubkey(),
+        &context.payer.pubkey(),
+        &data_bucket,
+        new_size as u32,
+    );
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::InvalidBucketSize))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.meta.size, SIZE);
+}
+
+#[tokio::test]
+async fn test_shrink_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let authority = context.authority();
+    let instruction = instruction::extend_bucket(
+        &context.program_id,
+        &authority.pubkey(),
+        &context.payer.pubkey(),
+        &data_bucket,
+        SIZE - 1,
+    );
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::InvalidBucketSize))
+    );
+}
+
+#[tokio::test]
+async fn test_finalize_bucket() {
+    let mut context = Context::new().await;
+    let content = content();
+    let data_bucket = context.create_filled_bucket(&content).await;
+
+    context
+        .finalize_bucket(&data_bucket, &content)
+        .await
+        .unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert!(bucket.meta.sealed);
+    assert_eq!(bucket.data, content);
+    assert_eq!(bucket.meta.merkle_root, merkle::merkle_root(&content));
+
+    // Sealed buckets can no longer change.
+    let authority = context.authority();
+    assert_eq!(
+        context
+            .put_into_bucket(&authority, &data_bucket, 0, &chunk(9))
+            .await,
+        Err(uploader_error(UploaderError::SealedBucket))
+    );
+    assert_eq!(
+        context.finalize_bucket(&data_bucket, &content[..10]).await,
+        Err(uploader_error(UploaderError::SealedBucket))
+    );
+}
+
+#[tokio::test]
+async fn test_finalize_max_shard() {
+    let mut context = Context::new().await;
+    let content: Vec<u8> = (0..MAX_SHARD_SIZE).map(|i| (i / 7) as u8).collect();
+
+    let authority = context.authority();
+    let size = max_create_size(MAX_SHARD_SIZE);
+    let (instruction, data_bucket) = instruction::create_bucket(
+        &context.program_id,
+        &authority.pubkey(),
+        &context.payer.pubkey(),
+        SEED,
+        &content[..DATA_BUCKET_CHUNK_SIZE],
+        size as u32,
+        0,
+        BucketKind::Data,
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    let mut size = size;
+    while size < MAX_SHARD_SIZE {
+        size = max_extend_size(size, MAX_SHARD_SIZE);
+        let instruction = instruction::extend_bucket(
+            &context.program_id,
+            &authority.pubkey(),
+            &context.payer.pubkey(),
+            &data_bucket,
+            size as u32,
+        );
+        context.process(instruction, &authority).await.unwrap();
+    }
+
+    // Larger buckets could not be sealed.
+    let instruction = instruction::extend_bucket(
+        &context.program_id,
+        &authority.pubkey(),
+        &context.payer.pubkey(),
+        &data_bucket,
+        (MAX_SHARD_SIZE + DATA_BUCKET_CHUNK_SIZE) as u32,
+    );
+    assert_eq!(
+        context.process(instruction, &authority).await,
+        Err(uploader_error(UploaderError::InvalidBucketSize))
+    );
+
+    for (i, piece) in content.chunks(12 * DATA_BUCKET_CHUNK_SIZE).enumerate() {
+        if i % 32 == 0 {
+            context.refresh_blockhash().await;
+        }
+
+        let offset = (i * 12 * DATA_BUCKET_CHUNK_SIZE) as u32;
+        context
+            .put_into_bucket(&authority, &data_bucket, offset, piece)
+            .await
+            .unwrap();
+    }
+
+    // The native processor is not metered, the compute units hashing takes
+    // are checked against the budget by the shard unit tests.
+    context
+        .finalize_bucket(&data_bucket, &content)
+        .await
+        .unwrap();
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert!(bucket.meta.sealed);
+    assert_eq!(bucket.meta.merkle_root, merkle::merkle_root(&content));
+}
+
+#[tokio::test]
+async fn test_finalize_incomplete_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    assert_eq!(
+        context.finalize_bucket(&data_bucket, &chunk(1)).await,
+        Err(uploader_error(UploaderError::IncompleteBucket))
+    );
+    assert!(!context.bucket(&data_bucket).await.meta.sealed);
+}
+
+#[tokio::test]
+async fn test_finalize_with_wrong_hash() {
+    let mut context = Context::new().await;
+    let content = content();
+    let data_bucket = context.create_filled_bucket(&content).await;
+
+    let mut other = content.clone();
+    other[0] ^= 1;
+    assert_eq!(
+        context.finalize_bucket(&data_bucket, &other).await,
+        Err(uploader_error(UploaderError::HashMismatch))
+    );
+    assert!(!context.bucket(&data_bucket).await.meta.sealed);
+}
+
+#[tokio::test]
+async fn test_read_bucket() {
+    let mut context = Context::new().await;
+    let content = content();
+    let data_bucket = context.create_filled_bucket(&content).await;
+
+    // Failed transactions are not processed again, so each read differs.
+    let read = instruction::read_bucket(&context.program_id, &data_bucket, 0, 20);
+    assert_eq!(
+        context.process_with(read, &[]).await,
+        Err(uploader_error(UploaderError::UnsealedBucket))
+    );
+
+    context
+        .finalize_bucket(&data_bucket, &content)
+        .await
+        .unwrap();
+    let read = instruction::read_bucket(&context.program_id, &data_bucket, 10, 20);
+    context.process_with(read, &[]).await.unwrap();
+
+    let read = instruction::read_bucket(&context.program_id, &data_bucket, SIZE - 10, 20);
+    assert_eq!(
+        context.process_with(read, &[]).await,
+        Err(uploader_error(UploaderError::OffsetOutOfBounds))
+    );
+}
+
+#[tokio::test]
+async fn test_close_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+    let lamports = context.banks_client.get_balance(data_bucket).await.unwrap();
+
+    let recipient = Pubkey::new_unique();
+    let authority = context.authority();
+    let instruction = instruction::close_bucket(
+        &context.program_id,
+        &authority.pubkey(),
+        &data_bucket,
+        &recipient,
+    );
+    context.process(instruction, &authority).await.unwrap();
+
+    assert_eq!(
+        context.banks_client.get_account(data_bucket).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        lamports
+    );
+}
+
+#[tokio::test]
+async fn test_close_bucket_without_authority() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_bucket(&chunk(1)).await.unwrap();
+
+    let intruder = Keypair::new();
+    let instruction = instruction::close_bucket(
+        &context.program_id,
+        &intruder.pubkey(),
+        &data_bucket,
+        &intruder.pubkey(),
+    );
+    assert_eq!(
+        context.process(instruction, &intruder).await,
+        Err(uploader_error(UploaderError::WrongAuthority))
+    );
+
+    let bucket = context.bucket(&data_bucket).await;
+    assert_eq!(bucket.data, chunk(1));
+}
+
+#[tokio::test]
+async fn test_expire_bucket() {
+    let mut context = Context::new().await;
+    let data_bucket = context.create_expiring_bucket(&chunk(1), 10).await.unwrap();
+    let last_updated_slot = context.bucket(&data_bucket).await.meta.last_updated_slot;
+
+    // Failed transactions are not processed again, so each attempt differs.
+    let early =
+        instruction::expire_bucket(&context.program_id, &data_bucket, &Pubkey::new_unique());
+    assert_eq!(
+        context.process_with(early, &[]).await,
+        Err(uploader_error(UploaderError::BucketNotExpired))
+    );
+
+    context
+        .test_context
+        .warp_to_slot(last_updated_slot + 10)
+        .unwrap();
+
+    // The rent can only be refunded to the payer.
+    let other =
+        instruction::expire_bucket(&context.program_id, &data_bucket, &Pubkey::new_unique());
+    assert_eq!(
+        context.process_with(other, &[]).await,
+        Err(uploader_error(UploaderError::InvalidRecipient))
+    );
+
+    let expire =
+        instruction::expire_bucket(&context.program_id, &data_bucket, &context.payer.pubkey());
+    context.process_with(expire, &[]).await.unwrap();
+    assert_eq!(
+        context.banks_client.get_account(data_bucket).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_expire_sealed_bucket() {
+    let mut context = Context::new().await;
+    let content = content();
+    let data_bucket = context.create_filled_bucket(&content).await;
+    context
+        .finalize_bucket(&data_bucket, &content)
+        .await
+        .unwrap();
+
+    // No expiry was requested, and sealed buckets never expire anyway.
+    let expire =
+        instruction::expire_bucket(&context.program_id, &data_bucket, &context.payer.pubkey());
+    assert_eq!(
+        context.process_with(expire, &[]).await,
+        Err(uploader_error(UploaderError::BucketNotExpired))
+    );
+    assert!(context.bucket(&data_bucket).await.meta.sealed);
+}
@@ -1,9 +1,16 @@
 use tokio::time::sleep;
 use {
     anyhow::Result,
+    borsh::BorshDeserialize,
     clap::{
-        crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, SubCommand,
+        crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, ArgMatches,
+        SubCommand,
     },
+    eclipse_aleo_verifier::{
+        instruction::{self as verifier_instruction, BatchItem, BatchItemResult, MAX_BATCH_SIZE},
+        state::{find_verified_address, AleoTxContext, AleoVerified},
+    },
+    eclipse_uploader::state::DataBucketMeta,
     jsonrpsee::{
         http_client::{HttpClient, HttpClientBuilder},
         rpc_params,
@@ -12,22 +19,22 @@ use {
     serde::{Deserialize, Serialize},
     snarkvm::dpc::testnet2::Testnet2,
     snarkvm::prelude::{Block, Transaction as SnarkVMTransaction},
-    snarkvm::utilities::ToBytes,
+    snarkvm::utilities::{FromBytes, ToBytes},
+    solana_account_decoder::UiAccountEncoding,
     solana_clap_utils::{
         input_parsers::{keypair_of, value_of},
         input_validators::{is_keypair, is_url},
     },
-    solana_client::rpc_client::RpcClient,
-    solana_program::{
-        instruction::{AccountMeta, Instruction},
-        message::Message,
-        pubkey::Pubkey,
-        system_program,
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
     },
+    solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey},
     solana_sdk::{
         signature::Signer, signer::keypair::Keypair, transaction::Transaction as SolanaTransaction,
     },
-    std::{process::exit, str::FromStr, time::Duration},
+    std::{process::exit, time::Duration},
 };
 
 mod aleo_proof;
@@ -106,7 +113,25 @@ async fn main() -> Result<()> {
                         .long("uploader_program_id")
                         .value_name("PUBKEY")
                         .takes_value(true)
-                        .help("Eclipse on-chain uploader program id"),
+                        .help("Eclipse on-chain uploader program id [default: the uploader crate id]"),
+                )
+                .arg(
+                    Arg::with_name("verifier_program_id")
+                        .long("verifier_program_id")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Eclipse on-chain Aleo verifier program id"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("close_buckets")
+                .about("Close uploaded data buckets whose Aleo Transaction has been verified")
+                .arg(
+                    Arg::with_name("uploader_program_id")
+                        .long("uploader_program_id")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Eclipse on-chain uploader program id [default: the uploader crate id]"),
                 )
                 .arg(
                     Arg::with_name("verifier_program_id")
@@ -116,6 +141,41 @@ async fn main() -> Result<()> {
                         .help("Eclipse on-chain Aleo verifier program id"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("set_verifier_config")
+                .about("Set the uploader program and native Aleo verifier version the verifier uses")
+                .arg(
+                    Arg::with_name("verifier_program_id")
+                        .long("verifier_program_id")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Eclipse on-chain Aleo verifier program id"),
+                )
+                .arg(
+                    Arg::with_name("uploader_program_id")
+                        .long("uploader_program_id")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Eclipse on-chain uploader program id [default: the uploader crate id]"),
+                )
+                .arg(
+                    Arg::with_name("aleo_verifier_version")
+                        .long("aleo_verifier_version")
+                        .value_name("VERSION")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Version of the native Aleo verifier; 1 and above return the transaction id"),
+                )
+                .arg(
+                    Arg::with_name("upgrade_authority")
+                        .long("upgrade_authority")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Upgrade authority keypair path of the verifier program"),
+                ),
+        )
         .get_matches();
     let eclipse = {
         let cli_config = if let Some(config_file) = matches.value_of("config_file") {
@@ -145,21 +205,49 @@ async fn main() -> Result<()> {
     let uploader_program_id;
     let _ = match matches.subcommand() {
         ("verify_proofs", Some(args)) => {
-            uploader_program_id = Pubkey::new(
-                &bs58::decode(value_of::<String>(args, "uploader_program_id").unwrap())
+            uploader_program_id = uploader_program_id_of(args);
+            verifier_program_id = Pubkey::new(
+                &bs58::decode(value_of::<String>(args, "verifier_program_id").unwrap())
+                    .into_vec()
+                    .unwrap(),
+            );
+            eclipse
+                .verify_proofs(&uploader_program_id, &verifier_program_id)
+                .await
+        }
+        ("close_buckets", Some(args)) => {
+            uploader_program_id = uploader_program_id_of(args);
+            verifier_program_id = Pubkey::new(
+                &bs58::decode(value_of::<String>(args, "verifier_program_id").unwrap())
                     .into_vec()
                     .unwrap(),
             );
+            eclipse
+                .close_buckets(&uploader_program_id, &verifier_program_id)
+                .await
+        }
+        ("set_verifier_config", Some(args)) => {
+            uploader_program_id = uploader_program_id_of(args);
             verifier_program_id = Pubkey::new(
                 &bs58::decode(value_of::<String>(args, "verifier_program_id").unwrap())
                     .into_vec()
                     .unwrap(),
             );
-            eclipse.verify_proofs(&uploader_program_id, &verifier_program_id)
+            let aleo_verifier_version =
+                value_t!(args, "aleo_verifier_version", u8).expect("invalid aleo verifier version");
+            let upgrade_authority =
+                keypair_of(args, "upgrade_authority").expect("invalid upgrade authority keypair");
+            eclipse
+                .set_verifier_config(
+                    &verifier_program_id,
+                    &upgrade_authority,
+                    uploader_program_id,
+                    aleo_verifier_version,
+                )
+                .await
         }
         _ => unreachable!(),
     }
-    .await
     .map_err(|err| {
         eprintln!("{err}");
         exit(1);
@@ -168,6 +256,14 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Parses the `uploader_program_id` argument, defaulting to the id the
+/// uploader crate is built with.
+fn uploader_program_id_of(args: &ArgMatches) -> Pubkey {
+    args.value_of("uploader_program_id")
+        .map(|id| id.parse().expect("invalid uploader program id"))
+        .unwrap_or_else(eclipse_uploader::id)
+}
+
 impl Eclipse {
     async fn verify_proofs(
         &self,
@@ -207,12 +303,112 @@ impl Eclipse {
         }
     }
 
+    async fn close_buckets(
+        &self,
+        uploader_program_id: &Pubkey,
+        verifier_program_id: &Pubkey,
+    ) -> Result<()> {
+        let author = self.author_keypair.pubkey();
+
+        println!("Fetching data buckets of {author:?}");
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp {
+                offset: DataBucketMeta::AUTHORITY_OFFSET,
+                bytes: MemcmpEncodedBytes::Base58(author.to_string()),
+                encoding: None,
+            })]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let buckets = self
+            .solana_client
+            .get_program_accounts_with_config(uploader_program_id, config)?;
+
+        for (bucket_pubkey, account) in buckets {
+            let meta = match DataBucketMeta::unpack(&account.data) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    println!("skipping {bucket_pubkey:?}: failed to deserialize bucket: {err}");
+                    continue;
+                }
+            };
+
+            if meta.authority != Some(author) {
+                continue;
+            }
+
+            let (payload, shard_buckets) = match uploader::read_payload(
+                &self.solana_client,
+                uploader_program_id,
+                &account.data,
+            ) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    println!("skipping {bucket_pubkey:?}: failed to read payload: {err}");
+                    continue;
+                }
+            };
+
+            let tx = match SnarkVMTransaction::<Testnet2>::from_bytes_le(&payload) {
+                Ok(tx) => tx,
+                Err(err) => {
+                    println!(
+                        "skipping {bucket_pubkey:?}: failed to deserialize transaction: {err}"
+                    );
+                    continue;
+                }
+            };
+
+            // Only close the bucket once the verification record exists.
+            let tx_id_bytes = tx.transaction_id().to_bytes_le()?;
+            let (state_account_pubkey, _) = find_verified_address(
+                verifier_program_id,
+                &tx_id_bytes.as_slice().try_into()?,
+                &author,
+            );
+            let verified = self
+                .solana_client
+                .get_account_with_commitment(
+                    &state_account_pubkey,
+                    self.solana_client.commitment(),
+                )?
+                .value
+                .and_then(|account| AleoVerified::try_from_slice(&account.data).ok())
+                .map_or(false, |record| record.tx_id[..] == tx_id_bytes[..]);
+            if !verified {
+                println!("skipping {bucket_pubkey:?}: transaction is not verified yet");
+                continue;
+            }
+
+            for bucket in std::iter::once(&bucket_pubkey).chain(&shard_buckets) {
+                uploader::close(
+                    &self.solana_client,
+                    uploader_program_id,
+                    &self.author_keypair,
+                    &self.payer_keypair,
+                    bucket,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn process_block(
         &self,
         block: &Block<Testnet2>,
         uploader_program_id: &Pubkey,
         verifier_program_id: &Pubkey,
     ) -> anyhow::Result<()> {
+        let block_height = block.height();
+        let block_hash: [u8; 32] = block.hash().to_bytes_le()?.as_slice().try_into()?;
+
+        // Transactions are uploaded one by one, then verified in batches.
+        let mut items = Vec::new();
         for tx_id in block.transactions().transaction_ids() {
             let response: Result<serde_json::Value, _> = self
                 .snarkos_client
@@ -233,27 +429,20 @@ impl Eclipse {
                         serde_json::from_value(response);
 
                     match response {
-                        Ok(tx) => {
-                            let tx_bytes = tx.transaction.to_bytes_le()?;
-
-                            // Upload Aleo transaction to Solana Account
-                            let tx_account = uploader::upload(
-                                &self.solana_client,
+                        Ok(tx) => match self
+                            .upload_transaction(
+                                &tx.transaction,
+                                block_height,
+                                block_hash,
                                 uploader_program_id,
-                                &self.author_keypair,
-                                &self.payer_keypair,
-                                tx_bytes.as_ref(),
-                            )
-                            .await?;
-
-                            let tx_id_bytes = tx.transaction.transaction_id().to_bytes_le()?;
-                            self.command_verify_proof(
-                                tx_id_bytes.as_ref(),
-                                verifier_program_id,
-                                &tx_account,
                             )
-                            .await?;
-                        }
+                            .await
+                        {
+                            Ok(item) => items.push(item),
+                            // One failed upload should not hold back the rest
+                            // of the block.
+                            Err(err) => println!("failed to upload transaction {tx_id}: {err}"),
+                        },
                         Err(err) => {
                             println!("error: failed to deserialize transaction: {err}");
                         }
@@ -265,52 +454,164 @@ impl Eclipse {
             }
         }
 
+        for batch in items.chunks(MAX_BATCH_SIZE) {
+            if let Err(err) = self.command_verify_proofs(batch, verifier_program_id).await {
+                println!("failed to verify transactions: {err}");
+            }
+        }
+
         Ok(())
     }
 
-    async fn command_verify_proof(
+    /// Uploads `tx`, pairing the bucket holding it with the item verifying it.
+    async fn upload_transaction(
         &self,
-        tx_id: &[u8],
+        tx: &SnarkVMTransaction<Testnet2>,
+        block_height: u32,
+        block_hash: [u8; 32],
+        uploader_program_id: &Pubkey,
+    ) -> anyhow::Result<(Pubkey, BatchItem)> {
+        let tx_bytes = tx.to_bytes_le()?;
+
+        // Upload Aleo transaction to Solana Account
+        let tx_account = uploader::upload(
+            &self.solana_client,
+            uploader_program_id,
+            &self.author_keypair,
+            &self.payer_keypair,
+            tx_bytes.as_ref(),
+        )
+        .await?;
+
+        let tx_id_bytes = tx.transaction_id().to_bytes_le()?;
+        let context = AleoTxContext {
+            block_height,
+            block_hash,
+            ledger_root: tx.ledger_root().to_bytes_le()?.as_slice().try_into()?,
+        };
+        let item = BatchItem {
+            tx_id: tx_id_bytes.as_slice().try_into()?,
+            context,
+        };
+
+        Ok((tx_account, item))
+    }
+
+    /// Verifies `items` in a single transaction, or one by one when the batch
+    /// as a whole fails, such as when one of the proofs is rejected.
+    async fn command_verify_proofs(
+        &self,
+        items: &[(Pubkey, BatchItem)],
         eclipse_program_id: &Pubkey,
-        tx_account: &Pubkey,
     ) -> anyhow::Result<()> {
-        let aleo_program_id = Pubkey::from_str("A1eoProof1111111111111111111111111111111111")
-            .expect("failed to set program_id");
-
-        // Account to store sucesssful verification
-        let (state_account_pubkey, _) = Pubkey::find_program_address(
-            &[
-                b"AleoTx".as_ref(),
-                tx_id,
-                self.author_keypair.pubkey().as_ref(),
-            ],
+        // Accounts to store sucesssful verifications
+        let (instruction, state_account_pubkeys) = verifier_instruction::verify_batch(
             eclipse_program_id,
+            &self.author_keypair.pubkey(),
+            items,
         );
+        let transaction = self.sign_transaction(instruction);
+
+        // The outcome of each item is only reported through return data,
+        // which is read from the logs of a simulation.
+        let simulation = self.solana_client.simulate_transaction(&transaction)?.value;
+        if let Some(err) = simulation.err {
+            println!("Batch verification failed: {err}, verifying one by one");
+            return self.command_verify_each(items, eclipse_program_id).await;
+        }
+        let results = simulation
+            .logs
+            .and_then(|logs| BatchItemResult::from_logs(eclipse_program_id, &logs));
 
-        let instruction = Instruction {
-            program_id: *eclipse_program_id,
-            accounts: vec![
-                AccountMeta::new(self.author_keypair.pubkey(), true),
-                AccountMeta::new(state_account_pubkey, false),
-                AccountMeta::new(*tx_account, false),
-                AccountMeta::new_readonly(aleo_program_id, false),
-                AccountMeta::new_readonly(system_program::id(), false),
-            ],
-            data: tx_id.to_vec(),
+        if let Err(err) = self.send_transaction(transaction).await {
+            println!("Batch verification failed: {err}, verifying one by one");
+            return self.command_verify_each(items, eclipse_program_id).await;
+        }
+
+        let results = match results {
+            Some(results) if results.len() == items.len() => results,
+            _ => {
+                println!("Verifications stored at Accounts: {state_account_pubkeys:?}");
+                return Ok(());
+            }
         };
+        for (state_account_pubkey, result) in state_account_pubkeys.iter().zip(results) {
+            match result {
+                BatchItemResult::Verified => {
+                    println!("Verification stored at Account: {state_account_pubkey:?}")
+                }
+                BatchItemResult::AlreadyVerified => {
+                    println!("Verification already stored at Account: {state_account_pubkey:?}")
+                }
+                BatchItemResult::Skipped { error } => {
+                    println!("Verification for {state_account_pubkey:?} skipped: error {error}")
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verifies each of `items` in its own transaction, so that a failing one
+    /// does not hold back the others.
+    async fn command_verify_each(
+        &self,
+        items: &[(Pubkey, BatchItem)],
+        eclipse_program_id: &Pubkey,
+    ) -> anyhow::Result<()> {
+        for (tx_account, item) in items {
+            let (instruction, state_account_pubkey) = verifier_instruction::verify_transaction(
+                eclipse_program_id,
+                &self.author_keypair.pubkey(),
+                tx_account,
+                item.tx_id,
+                item.context,
+            );
+            let transaction = self.sign_transaction(instruction);
+
+            match self.send_transaction(transaction).await {
+                Ok(()) => println!("Verification stored at Account: {state_account_pubkey:?}"),
+                Err(err) => println!("Verification for {state_account_pubkey:?} failed: {err}"),
+            }
+        }
 
+        Ok(())
+    }
+
+    /// Points the verifier at `uploader_program_id` and the native Aleo
+    /// verifier version it runs against.
+    async fn set_verifier_config(
+        &self,
+        verifier_program_id: &Pubkey,
+        upgrade_authority: &Keypair,
+        uploader_program_id: Pubkey,
+        aleo_verifier_version: u8,
+    ) -> Result<()> {
+        let instruction = verifier_instruction::set_config(
+            verifier_program_id,
+            &upgrade_authority.pubkey(),
+            uploader_program_id,
+            aleo_verifier_version,
+        );
+        let latest_blockhash = self.solana_client.get_latest_blockhash()?;
+        let message = Message::new(&[instruction], Some(&upgrade_authority.pubkey()));
+        let transaction = SolanaTransaction::new(&[upgrade_authority], message, latest_blockhash);
+        self.send_transaction(transaction).await?;
+
+        println!(
+            "Verifier config set: uploader {uploader_program_id}, Aleo verifier version {aleo_verifier_version}"
+        );
+        Ok(())
+    }
+
+    fn sign_transaction(&self, instruction: Instruction) -> SolanaTransaction {
         let latest_blockhash = self
             .solana_client
             .get_latest_blockhash()
             .expect("failed to fetch latest blockhash");
 
         let message = Message::new(&[instruction], Some(&self.author_keypair.pubkey()));
-        let transaction =
-            SolanaTransaction::new(&[&self.author_keypair], message, latest_blockhash);
-
-        self.send_transaction(transaction).await?;
-        println!("Verification stored at Account: {state_account_pubkey:?}");
-        Ok(())
+        SolanaTransaction::new(&[&self.author_keypair], message, latest_blockhash)
     }
 
     async fn send_transaction(
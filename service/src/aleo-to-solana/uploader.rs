@@ -1,24 +1,38 @@
 use {
+    eclipse_uploader::{
+        instruction, merkle,
+        shard::{self, Shard, ShardIndex, MAX_SHARD_SIZE},
+        state::{
+            content_seed, find_bucket_address, max_create_size, max_extend_size, BucketKind,
+            DataBucket, DATA_BUCKET_CHUNK_SIZE,
+        },
+    },
     solana_client::rpc_client::RpcClient,
     solana_sdk::{
-        instruction::{AccountMeta, Instruction},
-        message::Message,
-        pubkey::Pubkey,
-        signer::keypair::Keypair,
-        signer::Signer,
-        system_program,
-        transaction::Transaction,
+        instruction::Instruction, message::Message, packet::PACKET_DATA_SIZE, pubkey::Pubkey,
+        signer::keypair::Keypair, signer::Signer, transaction::Transaction,
     },
+    std::{ops::Range, time::Duration},
+    tokio::time::sleep,
 };
 
-const MAX_CHUNK_SIZE: usize = 768;
+/// Number of slots without writes after which an unfinished bucket may be
+/// expired by anyone, roughly a day.
+const BUCKET_EXPIRY_SLOTS: u64 = 216_000;
 
-fn adler32(data: &[u8]) -> u32 {
-    let mut hash = adler32::RollingAdler32::new();
-    hash.update_buffer(data);
-    hash.hash()
-}
+/// Number of times missing chunks are resent before giving up.
+const MAX_UPLOAD_ATTEMPTS: usize = 5;
+
+/// Length of the data put into a bucket when it is created, a whole number of
+/// chunks that leaves room in the transaction for the other arguments.
+const SEED_DATA_LEN: usize = 12 * DATA_BUCKET_CHUNK_SIZE;
+
+/// Number of times the bucket is polled for completeness per attempt.
+const MAX_COMPLETION_POLLS: usize = 20;
 
+/// Uploads `data` and returns the address of the bucket holding it. Data too
+/// large for a single account is split into shard buckets, listed in an index
+/// bucket at the address a single bucket would have had.
 pub async fn upload(
     solana_client: &RpcClient,
     program_id: &Pubkey,
@@ -26,91 +40,397 @@ pub async fn upload(
     payer: &Keypair,
     data: &[u8],
 ) -> anyhow::Result<Pubkey> {
-    let data = data.to_vec();
+    let seed = content_seed(data);
+    if data.len() <= MAX_SHARD_SIZE {
+        return upload_bucket(
+            solana_client,
+            program_id,
+            author,
+            payer,
+            data,
+            seed,
+            BucketKind::Data,
+        )
+        .await;
+    }
+
+    let mut shards = Vec::new();
+    for shard in data.chunks(MAX_SHARD_SIZE) {
+        let bucket = upload_bucket(
+            solana_client,
+            program_id,
+            author,
+            payer,
+            shard,
+            content_seed(shard),
+            BucketKind::Data,
+        )
+        .await?;
+        shards.push(Shard {
+            bucket,
+            len: shard.len() as u32,
+        });
+    }
+
+    let index = ShardIndex { shards }.pack();
+    upload_bucket(
+        solana_client,
+        program_id,
+        author,
+        payer,
+        &index,
+        seed,
+        BucketKind::Index,
+    )
+    .await
+}
+
+/// Uploads `data` into a single bucket derived from `seed` and seals it.
+///
+/// Uploading the same data again reuses the bucket: a sealed one is returned
+/// as is, an unsealed one, left behind by an interrupted upload, is resumed.
+async fn upload_bucket(
+    solana_client: &RpcClient,
+    program_id: &Pubkey,
+    author: &Keypair,
+    payer: &Keypair,
+    data: &[u8],
+    seed: [u8; 32],
+    kind: BucketKind,
+) -> anyhow::Result<Pubkey> {
     let total_size = data.len();
-    let (mut chunk, mut data) = data.split_at(768);
-
-    // Data Bucket Account
-    let adler_csum = adler32(chunk);
-    let (data_bucket_account_pubkey, bump_seed) = Pubkey::find_program_address(
-        &[
-            b"solana-data-packer".as_ref(),
-            author.pubkey().as_ref(),
-            adler_csum.to_be_bytes().as_slice(),
-        ],
+    let (data_bucket_account_pubkey, _) = find_bucket_address(program_id, &author.pubkey(), &seed);
+
+    let existing = solana_client
+        .get_account_with_commitment(&data_bucket_account_pubkey, solana_client.commitment())?
+        .value;
+    let (mut size, mut missing) = match existing {
+        Some(account) => {
+            if account.owner != *program_id {
+                anyhow::bail!("{data_bucket_account_pubkey:?} is not a data bucket");
+            }
+
+            let bucket = DataBucket::unpack(&account.data)?;
+            if bucket.meta.sealed {
+                if bucket.meta.merkle_root != merkle::merkle_root(data) {
+                    anyhow::bail!(
+                        "{data_bucket_account_pubkey:?} is sealed with different content"
+                    );
+                }
+
+                println!("Reusing sealed data bucket: {data_bucket_account_pubkey:?}");
+                return Ok(data_bucket_account_pubkey);
+            }
+
+            let size = bucket.meta.size as usize;
+            if bucket.meta.authority != Some(author.pubkey()) || size > total_size {
+                anyhow::bail!("cannot resume the upload to {data_bucket_account_pubkey:?}");
+            }
+
+            println!("Resuming upload to data bucket: {data_bucket_account_pubkey:?}");
+            let mut missing = bucket.missing_ranges();
+            missing.push(size..total_size);
+            (size, missing)
+        }
+        None => {
+            // Buckets can only be allocated in small steps, so large ones are
+            // created small and extended to their size.
+            let seed_data = &data[..total_size.min(SEED_DATA_LEN)];
+            let size = max_create_size(total_size);
+            let (instruction, _) = instruction::create_bucket(
+                program_id,
+                &author.pubkey(),
+                &payer.pubkey(),
+                seed,
+                seed_data,
+                size as u32,
+                BUCKET_EXPIRY_SLOTS,
+                kind,
+            );
+
+            println!("Saving data to account: {data_bucket_account_pubkey:?}");
+
+            let latest_blockhash = solana_client
+                .get_latest_blockhash()
+                .expect("failed to fetch latest blockhash");
+
+            let message = Message::new(&[instruction], Some(&author.pubkey()));
+            let transaction = Transaction::new(&[author, payer], message, latest_blockhash);
+
+            send_transaction(solana_client, transaction).await?;
+
+            (size, vec![seed_data.len()..total_size])
+        }
+    };
+
+    while size < total_size {
+        size = max_extend_size(size, total_size);
+        let instruction = instruction::extend_bucket(
+            program_id,
+            &author.pubkey(),
+            &payer.pubkey(),
+            &data_bucket_account_pubkey,
+            size as u32,
+        );
+
+        let latest_blockhash = solana_client
+            .get_latest_blockhash()
+            .expect("failed to fetch latest blockhash");
+
+        let message = Message::new(&[instruction], Some(&author.pubkey()));
+        let transaction = Transaction::new(&[author, payer], message, latest_blockhash);
+
+        send_transaction(solana_client, transaction).await?;
+    }
+
+    // Then send rest of the data all at once. The bucket keeps track of the
+    // received chunks, so whatever got lost is resent on the next attempt.
+    missing.retain(|range| !range.is_empty());
+    for _ in 0..MAX_UPLOAD_ATTEMPTS {
+        if missing.is_empty() {
+            break;
+        }
+
+        let latest_blockhash = solana_client
+            .get_latest_blockhash()
+            .expect("failed to fetch latest blockhash");
+
+        // Segments are cut at chunk boundaries and packed into transactions,
+        // so that the gaps left by lost transactions are patched together.
+        println!("Sending {} missing ranges...", missing.len());
+        let put_segments = |batch: &[Range<usize>]| {
+            put_segments_instruction(
+                program_id,
+                author,
+                payer,
+                &data_bucket_account_pubkey,
+                data,
+                batch,
+            )
+        };
+        let batch_size =
+            |batch: &[Range<usize>]| transaction_size(put_segments(batch), &author.pubkey());
+        for batch in batch_segments(&missing, batch_size) {
+            let instruction = put_segments(&batch);
+
+            let message = Message::new(&[instruction], Some(&author.pubkey()));
+            let transaction = Transaction::new(&[author, payer], message, latest_blockhash);
+
+            if let Err(err) = solana_client.send_transaction(&transaction) {
+                println!("failed to send segments {batch:?}: {err}");
+            }
+        }
+
+        missing = wait_for_chunks(solana_client, &data_bucket_account_pubkey).await?;
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "failed to upload {} ranges to {data_bucket_account_pubkey:?}",
+            missing.len()
+        );
+    }
+
+    // Finally seal the bucket so that consumers can rely on its content.
+    let instruction = instruction::finalize_bucket(
         program_id,
+        &author.pubkey(),
+        &data_bucket_account_pubkey,
+        total_size as u32,
+        merkle::merkle_root(data),
     );
 
-    println!("Saving data to account: {data_bucket_account_pubkey:?}");
+    let latest_blockhash = solana_client
+        .get_latest_blockhash()
+        .expect("failed to fetch latest blockhash");
 
-    // First create the data bucket.
-    let serialized_bucket = eclipse_uploader::instruction::ProgramInstruction::CreateBucket {
-        data: chunk,
-        size: total_size as u32,
-        bump_seed,
-    }
-    .serialize();
-
-    let instruction = Instruction {
-        program_id: *program_id,
-        accounts: vec![
-            AccountMeta::new(author.pubkey(), true),
-            AccountMeta::new(payer.pubkey(), true),
-            AccountMeta::new(data_bucket_account_pubkey, false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-        data: serialized_bucket,
-    };
+    let message = Message::new(&[instruction], Some(&author.pubkey()));
+    let transaction = Transaction::new(&[author], message, latest_blockhash);
+
+    send_transaction(solana_client, transaction).await?;
+
+    Ok(data_bucket_account_pubkey)
+}
+
+pub async fn close(
+    solana_client: &RpcClient,
+    program_id: &Pubkey,
+    author: &Keypair,
+    payer: &Keypair,
+    data_bucket_account_pubkey: &Pubkey,
+) -> anyhow::Result<()> {
+    println!("Closing data bucket account: {data_bucket_account_pubkey:?}");
+
+    let instruction = instruction::close_bucket(
+        program_id,
+        &author.pubkey(),
+        data_bucket_account_pubkey,
+        &payer.pubkey(),
+    );
 
     let latest_blockhash = solana_client
         .get_latest_blockhash()
         .expect("failed to fetch latest blockhash");
 
     let message = Message::new(&[instruction], Some(&author.pubkey()));
-    let transaction = Transaction::new(&[author, payer], message, latest_blockhash);
+    let transaction = Transaction::new(&[author], message, latest_blockhash);
 
     send_transaction(solana_client, transaction).await?;
+    Ok(())
+}
 
-    let mut offset = chunk.len();
+/// Reads the payload of a sealed bucket, following the shards listed by an
+/// index bucket. Returns the payload along with the shard buckets it spans.
+pub fn read_payload(
+    solana_client: &RpcClient,
+    program_id: &Pubkey,
+    bucket_data: &[u8],
+) -> anyhow::Result<(Vec<u8>, Vec<Pubkey>)> {
+    let bucket = DataBucket::unpack(bucket_data)?;
+    let shard_buckets: Vec<Pubkey> = match bucket.meta.kind {
+        BucketKind::Data => Vec::new(),
+        BucketKind::Index => ShardIndex::unpack(&bucket.data)?
+            .shards
+            .iter()
+            .map(|shard| shard.bucket)
+            .collect(),
+    };
 
-    // Then send rest of the data.
-    while !data.is_empty() {
-        if data.len() > MAX_CHUNK_SIZE {
-            (chunk, data) = data.split_at(MAX_CHUNK_SIZE);
-        } else {
-            chunk = data;
-            data = &[];
+    let mut shards = Vec::with_capacity(shard_buckets.len());
+    for (shard_bucket, account) in shard_buckets
+        .iter()
+        .zip(solana_client.get_multiple_accounts(&shard_buckets)?)
+    {
+        match account {
+            Some(account) if account.owner == *program_id => {
+                shards.push((*shard_bucket, account.data))
+            }
+            _ => anyhow::bail!("shard bucket {shard_bucket:?} is missing"),
         }
+    }
+
+    let shards: Vec<(Pubkey, &[u8])> = shards
+        .iter()
+        .map(|(shard_bucket, data)| (*shard_bucket, data.as_slice()))
+        .collect();
+    let payload = shard::read_payload(bucket_data, &shards)?;
+
+    Ok((payload, shard_buckets))
+}
+
+/// Creates the instruction putting the `segments` of `data` into the bucket.
+fn put_segments_instruction(
+    program_id: &Pubkey,
+    author: &Keypair,
+    payer: &Keypair,
+    data_bucket_account_pubkey: &Pubkey,
+    data: &[u8],
+    segments: &[Range<usize>],
+) -> Instruction {
+    match segments {
+        [segment] => instruction::put_into_bucket(
+            program_id,
+            &author.pubkey(),
+            &payer.pubkey(),
+            data_bucket_account_pubkey,
+            segment.start as u32,
+            &data[segment.clone()],
+            None,
+        ),
+        _ => instruction::put_segments(
+            program_id,
+            &author.pubkey(),
+            &payer.pubkey(),
+            data_bucket_account_pubkey,
+            segments
+                .iter()
+                .map(|segment| (segment.start as u32, &data[segment.clone()]))
+                .collect(),
+            None,
+        ),
+    }
+}
+
+/// Serialized size of a transaction made of `instruction` and paid by
+/// `fee_payer`.
+fn transaction_size(instruction: Instruction, fee_payer: &Pubkey) -> usize {
+    let message = Message::new(&[instruction], Some(fee_payer));
+    let signatures = message.header.num_required_signatures as usize;
+
+    // The signature count is a single byte for fewer than 128 signatures.
+    1 + signatures * 64 + message.serialize().len()
+}
+
+/// Splits the content `ranges` into segments and groups them so that the
+/// `transaction_size` of each group stays within a packet. Segments are cut
+/// at chunk boundaries, so that they still cover whole chunks.
+fn batch_segments(
+    ranges: &[Range<usize>],
+    transaction_size: impl Fn(&[Range<usize>]) -> usize,
+) -> Vec<Vec<Range<usize>>> {
+    let mut batches: Vec<Vec<Range<usize>>> = vec![Vec::new()];
+
+    for range in ranges {
+        let mut start = range.start;
+        while start < range.end {
+            let batch = batches.last_mut().expect("there is always a batch");
+
+            // Room left for data once the segment itself is accounted for,
+            // rounded down to whole chunks unless the range ends before.
+            let with_segment = [batch.as_slice(), &[start..start]].concat();
+            let room = PACKET_DATA_SIZE.saturating_sub(transaction_size(&with_segment));
+            let mut end = range.end.min(start + room);
+            if end < range.end {
+                end = start + (end - start) / DATA_BUCKET_CHUNK_SIZE * DATA_BUCKET_CHUNK_SIZE;
+            }
+
+            // Length prefixes may grow with the data, so shrink the segment by
+            // whole chunks until the batch actually fits.
+            while end > start
+                && transaction_size(&[batch.as_slice(), &[start..end]].concat()) > PACKET_DATA_SIZE
+            {
+                end = start + (end - start - 1) / DATA_BUCKET_CHUNK_SIZE * DATA_BUCKET_CHUNK_SIZE;
+            }
+
+            if end == start {
+                assert!(
+                    !batch.is_empty(),
+                    "a single chunk does not fit into a transaction"
+                );
+                batches.push(Vec::new());
+                continue;
+            }
 
-        let serialized_bucket = eclipse_uploader::instruction::ProgramInstruction::PutIntoBucket {
-            data: chunk,
-            offset: offset as u32,
+            batch.push(start..end);
+            start = end;
         }
-        .serialize();
-
-        let instruction = Instruction {
-            program_id: *program_id,
-            accounts: vec![
-                AccountMeta::new(author.pubkey(), true),
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(data_bucket_account_pubkey, false),
-                AccountMeta::new_readonly(system_program::id(), false),
-            ],
-            data: serialized_bucket,
-        };
+    }
 
-        let latest_blockhash = solana_client
-            .get_latest_blockhash()
-            .expect("failed to fetch latest blockhash");
+    batches.retain(|batch| !batch.is_empty());
+    batches
+}
 
-        let message = Message::new(&[instruction], Some(&author.pubkey()));
-        let transaction = Transaction::new(&[author, payer], message, latest_blockhash);
+/// Polls the bucket until all chunks are received, returning the content
+/// ranges that are still missing when polling gives up.
+async fn wait_for_chunks(
+    solana_client: &RpcClient,
+    data_bucket_account_pubkey: &Pubkey,
+) -> anyhow::Result<Vec<Range<usize>>> {
+    let mut missing = Vec::new();
 
-        send_transaction(solana_client, transaction).await?;
-        offset += chunk.len();
+    for _ in 0..MAX_COMPLETION_POLLS {
+        sleep(Duration::from_millis(500)).await;
+
+        let account = solana_client.get_account(data_bucket_account_pubkey)?;
+        let bucket = DataBucket::unpack(&account.data)?;
+        if bucket.meta.is_complete() {
+            return Ok(Vec::new());
+        }
+
+        missing = bucket.missing_ranges();
     }
 
-    Ok(data_bucket_account_pubkey)
+    Ok(missing)
 }
 
 async fn send_transaction(